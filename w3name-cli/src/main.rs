@@ -1,9 +1,9 @@
-use std::{error::Error, fmt::Display, fs, path::PathBuf, process::exit};
+use std::{env, error::Error, fmt::Display, fs, num::NonZeroUsize, path::PathBuf, process::exit, time::Duration};
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use error_stack::{IntoReport, Result, ResultExt, Report};
 
-use w3name::{Name, Revision, W3NameClient, WritableName, error::{ClientError, APIError}};
+use w3name::{IpnsRecord, Name, Revision, ValidationOptions, Value, VanitySearch, W3NameClient, WritableName, Zeroizing, error::{ClientError, APIError}};
 
 #[derive(Parser)]
 #[clap(name = "w3name", version, about, long_about = None)]
@@ -17,22 +17,85 @@ struct Cli {
 enum Commands {
   /// Lookup the current value for a name record.
   Resolve {
-    /// The name identifier, e.g. "k51qzi5uqu5dka3tmn6ipgsrq1u2bkuowdwlqcw0vibledypt1y9y5i8v8xwvu"
+    /// The name identifier, e.g. "k51qzi5uqu5dka3tmn6ipgsrq1u2bkuowdwlqcw0vibledypt1y9y5i8v8xwvu".
+    ///
+    /// Base32 CIDs, base58 peer IDs, `/ipns/` paths and `ipns://` URLs are accepted too.
+    #[clap(value_parser)]
+    name: Name,
+
+    /// Print the value even if the record has expired.
+    #[clap(long, action)]
+    allow_expired: bool,
+
+    /// If the name has been retired with `rotate`, follow it to the current name.
+    #[clap(long, action)]
+    follow: bool,
+
+    /// If the value is an `/ipns/` path, keep resolving until it isn't.
+    #[clap(long, action, conflicts_with = "follow")]
+    recursive: bool,
+
+    /// The maximum number of names to resolve with `--recursive`.
+    #[clap(long, value_parser, default_value_t = w3name::DEFAULT_RESOLVE_DEPTH, requires = "recursive")]
+    max_depth: NonZeroUsize,
+  },
+
+  /// Check a name record and print a detailed report of each verification step.
+  ///
+  /// Unlike `resolve`, this shows why a record fails verification instead of just rejecting it.
+  Verify {
+    /// The name identifier, e.g. "k51qzi5uqu5dka3tmn6ipgsrq1u2bkuowdwlqcw0vibledypt1y9y5i8v8xwvu".
+    ///
+    /// Base32 CIDs, base58 peer IDs, `/ipns/` paths and `ipns://` URLs are accepted too.
     #[clap(value_parser)]
-    name: String,
+    name: Name,
+
+    /// Path to a binary (protobuf-encoded) record to verify, instead of fetching the latest record from w3name.
+    #[clap(short, long, value_parser, value_name = "RECORD_FILE")]
+    record: Option<PathBuf>,
+
+    /// Don't fail records whose validity period has ended.
+    #[clap(long, action)]
+    allow_expired: bool,
+
+    /// Fail records that don't have a v2 signature.
+    #[clap(long, action)]
+    require_v2: bool,
   },
 
   /// Publish a new value for a name, signed with the name's private key.
   Publish {
     /// Path to a key file (see the `create` command to make one).
+    ///
+    /// If the key file is encrypted, the password is read from the W3NAME_KEY_PASSWORD environment variable,
+    /// or prompted for if that isn't set.
     #[clap(short, long, value_parser, value_name = "KEY_FILE")]
     key: PathBuf,
 
     /// The value to publish.
+    ///
+    /// Values starting with `/ipfs/` or `/ipns/` must be valid content paths.
     #[clap(short, long, value_parser)]
     value: String,
   },
 
+  /// Retire a name in favor of a new one, e.g. because its key was compromised.
+  ///
+  /// This publishes a final record for the old name that points at `/ipns/<successor>`, with a statement signed by
+  /// the old key that endorses the successor. `resolve --follow` follows it to the new name.
+  Rotate {
+    /// Path to the key file of the name to retire.
+    ///
+    /// If the key file is encrypted, the password is read from the W3NAME_KEY_PASSWORD environment variable,
+    /// or prompted for if that isn't set.
+    #[clap(short, long, value_parser, value_name = "KEY_FILE")]
+    key: PathBuf,
+
+    /// The name that replaces it. Publish a value for it before rotating, so that followers find one.
+    #[clap(long, value_parser, value_name = "NAME")]
+    to: Name,
+  },
+
   /// Create a new public/private keypair and save it to disk.
   Create {
     /// Filename to write the key to.
@@ -41,25 +104,95 @@ enum Commands {
     /// where `<name>` is the string form of the public key.
     #[clap(short, long, value_parser)]
     output: Option<PathBuf>,
+
+    /// The type of key to generate.
+    #[clap(long, value_enum, default_value_t = KeyType::Ed25519)]
+    key_type: KeyType,
+
+    /// Encrypt the key file with a password.
+    ///
+    /// The password is read from the W3NAME_KEY_PASSWORD environment variable, or prompted for if that isn't set.
+    #[clap(long, action)]
+    encrypt: bool,
+
+    /// Search for a name that ends with this pattern of 0-9 and a-z (ed25519 keys only).
+    ///
+    /// Each character makes the search about 36 times slower. The search uses all CPU cores, and can be cancelled with Ctrl-C.
+    #[clap(long, value_parser, value_name = "PATTERN", conflicts_with = "contains")]
+    suffix: Option<String>,
+
+    /// Search for a name that contains this pattern after the fixed `k51qzi5uqu5d` prefix (ed25519 keys only).
+    #[clap(long, value_parser, value_name = "PATTERN")]
+    contains: Option<String>,
+  },
+
+  /// Import an existing private key and save it as a w3name key file.
+  ///
+  /// This lets you use keys made by other tools, e.g. `openssl genpkey -algorithm ed25519`.
+  Import {
+    /// Path to a PKCS#8 private key, either PEM or DER encoded.
+    ///
+    /// ed25519, secp256k1 and P-256 (ECDSA) keys are supported, plus RSA keys if w3name was built with the `rsa` feature.
+    #[clap(value_parser, value_name = "PRIVATE_KEY_FILE")]
+    input: PathBuf,
+
+    /// Filename to write the key to.
+    ///
+    /// If not given, will write to a file named `<name>.key`,
+    /// where `<name>` is the string form of the public key.
+    #[clap(short, long, value_parser)]
+    output: Option<PathBuf>,
+
+    /// Encrypt the key file with a password.
+    ///
+    /// The password is read from the W3NAME_KEY_PASSWORD environment variable, or prompted for if that isn't set.
+    #[clap(long, action)]
+    encrypt: bool,
   },
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum KeyType {
+  Ed25519,
+  Secp256k1,
+  Ecdsa,
+  /// 2048-bit RSA, only available with the `rsa` feature
+  #[cfg(feature = "rsa")]
+  Rsa,
+}
+
 #[tokio::main]
 async fn main() {
   let cli = Cli::parse();
 
   use Commands::*;
   let res = match &cli.command {
-    Resolve { name } => {
-      resolve(name).await
+    Resolve { name, allow_expired, follow, recursive, max_depth } => {
+      let recursive = recursive.then_some(*max_depth);
+      resolve(name, *allow_expired, *follow, recursive).await
+    }
+
+    Verify { name, record, allow_expired, require_v2 } => {
+      let options = ValidationOptions::new()
+        .allow_expired(*allow_expired)
+        .require_v2(*require_v2);
+      verify(name, record, &options).await
     }
 
     Publish { key, value } => {
       publish(key, value).await
     }
 
-    Create { output } => {
-      create(output)
+    Rotate { key, to } => {
+      rotate(key, to).await
+    }
+
+    Create { output, key_type, encrypt, suffix, contains } => {
+      create(output, *key_type, *encrypt, suffix.as_deref(), contains.as_deref()).await
+    }
+
+    Import { input, output, encrypt } => {
+      import(input, output, *encrypt)
     }
   };
 
@@ -69,18 +202,38 @@ async fn main() {
   }
 }
 
-async fn resolve(name_str: &str) -> Result<(), CliError> {
-  let client = W3NameClient::default();
-  let name = Name::parse(name_str).change_context(CliError)?;
-  match client.resolve(&name).await {
-    Ok(revision) => {
-      println!("{}", revision.value());
+async fn resolve(name: &Name, allow_expired: bool, follow: bool, recursive: Option<NonZeroUsize>) -> Result<(), CliError> {
+  let client = W3NameClient::default()
+    .with_validation_options(ValidationOptions::new().allow_expired(allow_expired));
+  let res = if let Some(depth) = recursive {
+    client.with_resolve_depth(depth).resolve_recursive(name).await.map(|resolution| {
+      let names: Vec<String> = resolution.names().map(Name::to_string).collect();
+      if names.len() > 1 {
+        eprintln!("resolved through: {}", names.join(" -> "));
+      }
+      resolution.value().to_vec()
+    })
+  } else if follow {
+    client.resolve_succession(name).await.map(|chain| {
+      if chain.is_rotated() {
+        let names: Vec<String> = chain.names().map(Name::to_string).collect();
+        eprintln!("followed rotated name: {}", names.join(" -> "));
+      }
+      chain.into_current().value().to_vec()
+    })
+  } else {
+    client.resolve(name).await.map(|revision| revision.value().to_vec())
+  };
+
+  match res {
+    Ok(value) => {
+      println!("{}", String::from_utf8_lossy(&value));
       Ok(())
     }
 
     Err(err_report) => {
       if is_404(&err_report) {
-        eprintln!("no record found for key {}", name_str);
+        eprintln!("no record found for key {}", name);
         Ok(())
       } else {
         Err(err_report.change_context(CliError))
@@ -89,28 +242,147 @@ async fn resolve(name_str: &str) -> Result<(), CliError> {
   }
 }
 
-fn create(output: &Option<PathBuf>) -> Result<(), CliError> {
-  let name = WritableName::new();
+async fn verify(name: &Name, record_file: &Option<PathBuf>, options: &ValidationOptions) -> Result<(), CliError> {
+  let record = match record_file {
+    Some(path) => {
+      let bytes = fs::read(path).report().change_context(CliError)?;
+      IpnsRecord::decode(&bytes).change_context(CliError)?
+    }
+    None => W3NameClient::default()
+      .fetch_record(name)
+      .await
+      .change_context(CliError)?,
+  };
+
+  let report = record.verification_report(name, options);
+  print!("{report}");
+  if !report.is_valid() {
+    exit(1);
+  }
+  Ok(())
+}
+
+async fn create(output: &Option<PathBuf>, key_type: KeyType, encrypt: bool, suffix: Option<&str>, contains: Option<&str>) -> Result<(), CliError> {
+  let search = match (suffix, contains) {
+    (Some(pattern), _) => Some(VanitySearch::suffix(pattern)),
+    (None, Some(pattern)) => Some(VanitySearch::contains(pattern)),
+    (None, None) => None,
+  }
+  .transpose()
+  .change_context(CliError)?;
+
+  let name = match (search, key_type) {
+    (Some(search), KeyType::Ed25519) => vanity_search(search).await?,
+    (Some(_), _) => {
+      return Err(Report::new(CliError).attach_printable("vanity names are only supported for ed25519 keys"))
+    }
+    (None, KeyType::Ed25519) => WritableName::new(),
+    (None, KeyType::Secp256k1) => WritableName::new_secp256k1(),
+    (None, KeyType::Ecdsa) => WritableName::new_ecdsa(),
+    #[cfg(feature = "rsa")]
+    (None, KeyType::Rsa) => WritableName::new_rsa(),
+  };
+  let output = write_key_file(&name, output, encrypt)?;
+  println!("wrote new keypair to {}", output.display());
+  Ok(())
+}
+
+/// Runs a vanity name search, printing progress to stderr until it finds a name or is interrupted with Ctrl-C.
+async fn vanity_search(search: VanitySearch) -> Result<WritableName, CliError> {
+  eprintln!(
+    "searching for a matching name, which takes {} keys on average",
+    format_count(search.expected_attempts())
+  );
+  let canceller = search.canceller();
+  let mut task = tokio::task::spawn_blocking(move || {
+    search.run(|progress| {
+      let remaining = progress
+        .expected_time_remaining()
+        .map_or("unknown".to_string(), format_duration);
+      let line = format!(
+        "tried {} keys at {}/s, expected time ~{} ({:.0}% chance by now)",
+        format_count(progress.attempts() as f64),
+        format_count(progress.attempts_per_second()),
+        remaining,
+        progress.probability() * 100.0
+      );
+      eprint!("\r{line:<80}");
+    })
+  });
+
+  let res = tokio::select! {
+    res = &mut task => res,
+    _ = tokio::signal::ctrl_c() => {
+      canceller.cancel();
+      task.await
+    }
+  };
+  eprintln!();
+  res.report().change_context(CliError)?.change_context(CliError)
+}
+
+/// Formats a large number with a metric suffix, e.g. 1.2M.
+fn format_count(n: f64) -> String {
+  match n {
+    n if n >= 1e12 => format!("{:.1}T", n / 1e12),
+    n if n >= 1e9 => format!("{:.1}G", n / 1e9),
+    n if n >= 1e6 => format!("{:.1}M", n / 1e6),
+    n if n >= 1e3 => format!("{:.1}k", n / 1e3),
+    n => format!("{n:.0}"),
+  }
+}
+
+/// Formats a duration with its two largest units, e.g. 1h 23m.
+fn format_duration(duration: Duration) -> String {
+  let secs = duration.as_secs();
+  let (days, hours, mins) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60);
+  match (days, hours, mins) {
+    (d, _, _) if d >= 365 * 100 => "more than 100 years".to_string(),
+    (d, h, _) if d > 0 => format!("{d}d {h}h"),
+    (_, h, m) if h > 0 => format!("{h}h {m}m"),
+    (_, _, m) => format!("{m}m {}s", secs % 60),
+  }
+}
+
+fn import(input: &PathBuf, output: &Option<PathBuf>, encrypt: bool) -> Result<(), CliError> {
+  let bytes = Zeroizing::new(fs::read(input).report().change_context(CliError)?);
+  let name = match std::str::from_utf8(&bytes) {
+    Ok(pem) if pem.trim_start().starts_with("-----BEGIN") => WritableName::from_pkcs8_pem(pem),
+    _ => WritableName::from_pkcs8_der(&bytes),
+  }
+  .change_context(CliError)?;
+
+  let output = write_key_file(&name, output, encrypt)?;
+  println!("imported key for {name} to {}", output.display());
+  Ok(())
+}
+
+/// Writes the key file for `name`, defaulting to `<name>.key`, and returns the path that was written.
+fn write_key_file(name: &WritableName, output: &Option<PathBuf>, encrypt: bool) -> Result<PathBuf, CliError> {
   let output = output
     .clone()
-    .unwrap_or_else(|| PathBuf::from(format!("{}.key", name.to_string())));
+    .unwrap_or_else(|| PathBuf::from(format!("{name}.key")));
 
-  let bytes = name
-    .keypair()
-    .to_protobuf_encoding()
-    .report()
-    .change_context(CliError)?;
-  fs::write(&output, bytes)
+  let bytes = if encrypt {
+    let password = new_password()?;
+    Zeroizing::new(name.encrypt(&password).change_context(CliError)?)
+  } else {
+    name.encode().change_context(CliError)?
+  };
+  fs::write(&output, &bytes)
     .report()
     .change_context(CliError)?;
-  println!("wrote new keypair to {}", output.display());
-  Ok(())
+  Ok(output)
 }
 
 async fn publish(key_file: &PathBuf, value: &str) -> Result<(), CliError> {
+  // catch typos in content paths before anything is signed
+  if value.starts_with("/ipfs/") || value.starts_with("/ipns/") {
+    Value::parse(value).change_context(CliError)?;
+  }
+
   let client = W3NameClient::default();
-  let key_bytes = fs::read(key_file).report().change_context(CliError)?;
-  let writable = WritableName::decode(&key_bytes).change_context(CliError)?;
+  let writable = read_key_file(key_file)?;
 
   // to avoid having to keep old revisions around, we first try to resolve and increment any existing records
   let new_revision = match client.resolve(&writable.to_name()).await {
@@ -134,12 +406,57 @@ async fn publish(key_file: &PathBuf, value: &str) -> Result<(), CliError> {
 
   println!(
     "published new value for key {}: {}",
-    writable.to_string(),
+    writable,
     value
   );
   Ok(())
 }
 
+async fn rotate(key_file: &PathBuf, successor: &Name) -> Result<(), CliError> {
+  let writable = read_key_file(key_file)?;
+  W3NameClient::default()
+    .rotate(&writable, successor)
+    .await
+    .change_context(CliError)?;
+
+  println!("retired key {} in favor of {}", writable, successor);
+  Ok(())
+}
+
+/// Reads a key file written by `create` or `import`, asking for the password if it's encrypted.
+fn read_key_file(key_file: &PathBuf) -> Result<WritableName, CliError> {
+  let key_bytes = Zeroizing::new(fs::read(key_file).report().change_context(CliError)?);
+  if WritableName::is_encrypted(&key_bytes) {
+    let password = password(&format!("password for {}: ", key_file.display()))?;
+    WritableName::decrypt(&key_bytes, &password).change_context(CliError)
+  } else {
+    WritableName::decode(&key_bytes).change_context(CliError)
+  }
+}
+
+/// The environment variable to read key file passwords from, for non-interactive use.
+const PASSWORD_ENV_VAR: &str = "W3NAME_KEY_PASSWORD";
+
+/// Returns the key file password from the environment, or prompts for it.
+fn password(prompt: &str) -> Result<Zeroizing<String>, CliError> {
+  if let Ok(password) = env::var(PASSWORD_ENV_VAR) {
+    return Ok(Zeroizing::new(password));
+  }
+  let password = rpassword::prompt_password(prompt).report().change_context(CliError)?;
+  Ok(Zeroizing::new(password))
+}
+
+/// Like [password], but asks twice when prompting, so that a typo doesn't lock the user out of a new key.
+fn new_password() -> Result<Zeroizing<String>, CliError> {
+  let new_password = password("new key file password: ")?;
+  if new_password.is_empty() {
+    return Err(Report::new(CliError).attach_printable("the password must not be empty"));
+  }
+  if new_password != password("confirm password: ")? {
+    return Err(Report::new(CliError).attach_printable("passwords don't match"));
+  }
+  Ok(new_password)
+}
 
 /// Returns true if the error report contains an [APIError] with a 404 status
 fn is_404(report: &Report<ClientError>) -> bool {
@@ -4,6 +4,9 @@ fn main() -> Result<()> {
   #[cfg(feature = "protoc-src")]
   std::env::set_var("PROTOC", protobuf_src::protoc());
 
-  prost_build::compile_protos(&["src/ipns/ipns_pb.proto"], &["src/ipns"])?;
+  prost_build::compile_protos(
+    &["src/ipns/ipns_pb.proto", "src/keys_pb.proto"],
+    &["src/ipns", "src"],
+  )?;
   Ok(())
 }
@@ -0,0 +1,161 @@
+//! An example [Signer] that keeps the private key in a separate process.
+//!
+//! The signing process loads a key file, writes its public key to stdout, and then signs every line it reads
+//! from stdin. Public keys, messages and signatures are base64 encoded, one per line. In a real deployment the
+//! signer would run as a different user or on a different machine; to keep the example self-contained, this
+//! program starts a copy of itself as the signing process.
+//!
+//! Create a key file with `w3name create`, then run:
+//!
+//! ```sh
+//! cargo run --example subprocess_signer -- <KEY_FILE> <VALUE>
+//! ```
+//!
+//! This signs and verifies a record for the value without publishing it. To publish it, pass the signer to
+//! `W3NameClient::publish` instead.
+
+use std::{
+  env,
+  error::Error,
+  io::{self, BufRead, Write},
+  process::{exit, Stdio},
+};
+
+use error_stack::{report, Result};
+use libp2p_core::identity::PublicKey;
+use tokio::{
+  io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+  process::{Child, ChildStdin, ChildStdout, Command},
+  sync::Mutex,
+};
+use w3name::{async_trait, error::SigningError, IpnsRecord, Revision, Signer, WritableName};
+
+/// A [Signer] that sends signing requests to a child process.
+struct SubprocessSigner {
+  public_key: PublicKey,
+  // requests and responses must not interleave, so only one signing request is in flight at a time
+  io: Mutex<SignerIo>,
+  child: Child,
+}
+
+struct SignerIo {
+  stdin: ChildStdin,
+  lines: Lines<BufReader<ChildStdout>>,
+}
+
+impl SignerIo {
+  async fn read_line(&mut self) -> io::Result<Vec<u8>> {
+    let line =
+      self.lines.next_line().await?.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::UnexpectedEof, "the signing process exited")
+      })?;
+    base64::decode(line.trim()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+  }
+
+  async fn request(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+    let line = format!("{}\n", base64::encode(data));
+    self.stdin.write_all(line.as_bytes()).await?;
+    self.stdin.flush().await?;
+    self.read_line().await
+  }
+}
+
+impl SubprocessSigner {
+  /// Starts the signing process, and waits for it to send its public key.
+  async fn spawn(command: &mut Command) -> io::Result<SubprocessSigner> {
+    let mut child = command
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .kill_on_drop(true)
+      .spawn()?;
+    let mut io = SignerIo {
+      stdin: child.stdin.take().expect("stdin is piped"),
+      lines: BufReader::new(child.stdout.take().expect("stdout is piped")).lines(),
+    };
+
+    let public_key = PublicKey::from_protobuf_encoding(&io.read_line().await?)
+      .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(SubprocessSigner {
+      public_key,
+      io: Mutex::new(io),
+      child,
+    })
+  }
+}
+
+#[async_trait]
+impl Signer for SubprocessSigner {
+  fn public_key(&self) -> PublicKey {
+    self.public_key.clone()
+  }
+
+  async fn sign(&self, data: &[u8]) -> Result<Vec<u8>, SigningError> {
+    let mut io = self.io.lock().await;
+    io.request(data)
+      .await
+      .map_err(|e| report!(SigningError).attach_printable(e.to_string()))
+  }
+}
+
+/// Runs the signing process for the key in `key_file`.
+fn serve(key_file: &str) -> std::result::Result<(), Box<dyn Error>> {
+  let bytes = std::fs::read(key_file)?;
+  let name = WritableName::decode(&bytes).map_err(|e| format!("{e:?}"))?;
+  let keypair = name.keypair();
+
+  let mut stdout = io::stdout().lock();
+  writeln!(
+    stdout,
+    "{}",
+    base64::encode(keypair.public().to_protobuf_encoding())
+  )?;
+  stdout.flush()?;
+
+  for line in io::stdin().lock().lines() {
+    let data = base64::decode(line?.trim())?;
+    let signature = keypair.sign(&data)?;
+    writeln!(stdout, "{}", base64::encode(signature))?;
+    stdout.flush()?;
+  }
+  Ok(())
+}
+
+async fn sign_value(key_file: &str, value: &str) -> std::result::Result<(), Box<dyn Error>> {
+  let mut command = Command::new(env::current_exe()?);
+  command.arg("--serve").arg(key_file);
+  let signer = SubprocessSigner::spawn(&mut command).await?;
+  let name = signer.name();
+  println!("signing for {name} in process {:?}", signer.child.id());
+
+  let revision = Revision::v0(&name, value);
+  let record = IpnsRecord::sign(&signer, &revision)
+    .await
+    .map_err(|e| format!("{e:?}"))?;
+  record.verify(&name).map_err(|e| format!("{e:?}"))?;
+
+  let encoded = record.encode().map_err(|e| format!("{e:?}"))?;
+  println!(
+    "signed a {} byte record: {}",
+    encoded.len(),
+    base64::encode(&encoded)
+  );
+  Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+  let args: Vec<String> = env::args().skip(1).collect();
+  let res = match args.as_slice() {
+    [flag, key_file] if flag == "--serve" => serve(key_file),
+    [key_file, value] => sign_value(key_file, value).await,
+    _ => {
+      eprintln!("usage: subprocess_signer <KEY_FILE> <VALUE>");
+      exit(2);
+    }
+  };
+
+  if let Err(err) = res {
+    eprintln!("{err}");
+    exit(1);
+  }
+}
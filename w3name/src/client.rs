@@ -1,3 +1,5 @@
+use std::num::NonZeroUsize;
+
 use error_stack::{report, IntoReport, Report, Result, ResultExt};
 use governor::{
   clock::DefaultClock,
@@ -8,21 +10,27 @@ use nonzero_ext::nonzero;
 use reqwest::{Client, Response, Url};
 
 use crate::{
-  error::{APIError, ClientError, HttpError, UnexpectedAPIResponse},
-  ipns::{
-    deserialize_ipns_entry, revision_from_ipns_entry, revision_to_ipns_entry, serialize_ipns_entry,
-    validate_ipns_entry,
+  error::{
+    APIError, ClientError, HttpError, RecordTooLarge, ResponseTooLarge, UnexpectedAPIResponse,
   },
-  Name, Revision, WritableName,
+  IpnsRecord, Name, RecursiveResolution, Revision, Signer, SigningOptions, Succession,
+  SuccessionChain, ValidationOptions, DEFAULT_RESOLVE_DEPTH, MAX_RECORD_SIZE,
 };
 
 const DEFAULT_ENDPOINT: &str = "https://name.web3.storage";
 const RATE_LIMIT_REQUESTS: u32 = 30;
 
+// A base64-encoded record takes 4/3 of its binary size. Leave plenty of room for the rest of the JSON response.
+const MAX_RESPONSE_SIZE: usize = MAX_RECORD_SIZE * 2 + 4096;
+
 pub struct W3NameClient {
   endpoint: Url,
   http: Client,
   limiter: RateLimiter<NotKeyed, InMemoryState, DefaultClock>,
+  validation: ValidationOptions,
+  signing: SigningOptions,
+  follow_succession: bool,
+  resolve_depth: NonZeroUsize,
 }
 
 impl W3NameClient {
@@ -33,15 +41,114 @@ impl W3NameClient {
       endpoint,
       http,
       limiter,
+      validation: ValidationOptions::default(),
+      signing: SigningOptions::default(),
+      follow_succession: false,
+      resolve_depth: DEFAULT_RESOLVE_DEPTH,
     }
   }
 
-  pub async fn publish(&self, name: &WritableName, revision: &Revision) -> Result<(), ClientError> {
-    let mut url = self.endpoint.clone();
-    url.set_path(format!("name/{}", name.to_string()).as_str());
+  /// Sets the [ValidationOptions] used to verify resolved records.
+  ///
+  /// By default, expired records are rejected. Use [ValidationOptions::require_v2] to reject records without a v2 signature.
+  pub fn with_validation_options(mut self, options: ValidationOptions) -> Self {
+    self.validation = options;
+    self
+  }
+
+  /// Sets whether [resolve](Self::resolve) follows [Succession]s from retired names to their current name.
+  ///
+  /// This is off by default, so `resolve` returns the latest revision of the name it was given.
+  /// Use [resolve_succession](Self::resolve_succession) to see every name in the chain.
+  pub fn with_follow_succession(mut self, follow: bool) -> Self {
+    self.follow_succession = follow;
+    self
+  }
+
+  /// Sets the maximum number of names that [resolve_recursive](Self::resolve_recursive) resolves in one call.
+  ///
+  /// Defaults to [DEFAULT_RESOLVE_DEPTH]. A depth of 1 only resolves the given name, and fails if its value
+  /// points at another name.
+  pub fn with_resolve_depth(mut self, depth: NonZeroUsize) -> Self {
+    self.resolve_depth = depth;
+    self
+  }
 
-    let entry = revision_to_ipns_entry(revision, name.keypair()).change_context(ClientError)?;
-    let encoded = serialize_ipns_entry(&entry).change_context(ClientError)?;
+  /// Sets the [SigningOptions] used to sign records in [publish](Self::publish).
+  ///
+  /// By default, records are signed with both v1 and v2 signatures.
+  pub fn with_signing_options(mut self, options: SigningOptions) -> Self {
+    self.signing = options;
+    self
+  }
+
+  /// Signs the given [Revision] and publishes it to w3name.
+  ///
+  /// The `signer` is usually a [WritableName](crate::WritableName), but any [Signer] can be used,
+  /// e.g. one that keeps the private key in a hardware token or another process.
+  pub async fn publish<S: Signer + ?Sized>(
+    &self,
+    signer: &S,
+    revision: &Revision,
+  ) -> Result<(), ClientError> {
+    let record = IpnsRecord::sign_with_options(signer, revision, &self.signing)
+      .await
+      .change_context(ClientError)?;
+    // signing already rejects records larger than MAX_RECORD_SIZE
+    let encoded = record.encode().change_context(ClientError)?;
+    self.post_record(&signer.name(), encoded).await
+  }
+
+  /// Retires the name of `signer` in favor of `successor`, and returns the published [Revision].
+  ///
+  /// This publishes a final record for the old name whose value is `/ipns/<successor>`, carrying a [Succession]
+  /// statement signed by the old key, so that resolvers can follow it to the new name with
+  /// [resolve_succession](Self::resolve_succession). The successor should have a published record of its own.
+  pub async fn rotate<S: Signer + ?Sized>(
+    &self,
+    signer: &S,
+    successor: &Name,
+  ) -> Result<Revision, ClientError> {
+    let name = signer.name();
+    let succession = Succession::sign(signer, successor)
+      .await
+      .change_context(ClientError)?;
+
+    let revision = match self.fetch_record(&name).await {
+      Ok(record) => {
+        // an expired record still has to be superseded by a higher sequence number
+        let options = self.validation.clone().allow_expired(true);
+        record
+          .verify_with_options(&name, &options)
+          .change_context(ClientError)?;
+        record
+          .to_revision(&name)
+          .change_context(ClientError)?
+          .increment_succession(&succession)
+      }
+      Err(err_report) if is_not_found(&err_report) => Revision::v0_succession(&name, &succession),
+      Err(err_report) => return Err(err_report),
+    };
+
+    self.publish(signer, &revision).await?;
+    Ok(revision)
+  }
+
+  /// Publishes an already-signed [IpnsRecord] for the given [Name].
+  ///
+  /// This is useful for forwarding records that were signed elsewhere, or re-publishing archived records.
+  /// The record is sent as-is; the w3name service will reject it if the signature doesn't match the name.
+  /// Records larger than [MAX_RECORD_SIZE] are rejected with a [RecordTooLarge] error before anything is sent.
+  pub async fn publish_record(&self, name: &Name, record: &IpnsRecord) -> Result<(), ClientError> {
+    // records signed elsewhere haven't been through the size check in signing
+    let encoded = record.encode().change_context(ClientError)?;
+    check_record_size(&encoded)?;
+    self.post_record(name, encoded).await
+  }
+
+  async fn post_record(&self, name: &Name, encoded: Vec<u8>) -> Result<(), ClientError> {
+    let mut url = self.endpoint.clone();
+    url.set_path(format!("name/{name}").as_str());
     let body = base64::encode(encoded);
 
     self.limiter.until_ready().await;
@@ -64,8 +171,70 @@ impl W3NameClient {
   }
 
   pub async fn resolve(&self, name: &Name) -> Result<Revision, ClientError> {
+    if self.follow_succession {
+      return Ok(self.resolve_succession(name).await?.into_current());
+    }
+    let record = self.resolve_record(name).await?;
+    record.to_revision(name).change_context(ClientError)
+  }
+
+  /// Resolves the given [Name], and keeps resolving while the value is an `/ipns/<name>` path.
+  ///
+  /// Every record along the way is verified. The returned [RecursiveResolution] has the final value, along with the
+  /// revision of each name that was resolved. Resolution fails with a [ResolutionCycle](crate::error::ResolutionCycle)
+  /// error if a name points back at a name that was already resolved, and with a
+  /// [ResolutionDepthExceeded](crate::error::ResolutionDepthExceeded) error if it needs more names than the
+  /// [resolve depth](Self::with_resolve_depth).
+  ///
+  /// Since a rotated name points at its successor, this follows [Succession]s too, but doesn't check their
+  /// statements. Use [resolve_succession](Self::resolve_succession) for that.
+  pub async fn resolve_recursive(&self, name: &Name) -> Result<RecursiveResolution, ClientError> {
+    let mut resolution = RecursiveResolution::new(self.resolve_depth.get());
+    let mut next = Some(name.clone());
+    while let Some(name) = next {
+      let record = self.resolve_record(&name).await?;
+      let revision = record.to_revision(&name).change_context(ClientError)?;
+      next = resolution.push(revision).change_context(ClientError)?;
+    }
+    Ok(resolution)
+  }
+
+  /// Resolves the given [Name], and follows any [Succession]s to the current name.
+  ///
+  /// The returned [SuccessionChain] has the latest revision of every name along the way, so callers can tell
+  /// that a name was rotated and update their references. Each succession must be signed by the key of the
+  /// name it retires, and chains that loop back on themselves are rejected with an
+  /// [InvalidSuccession](crate::error::InvalidSuccession) error.
+  pub async fn resolve_succession(&self, name: &Name) -> Result<SuccessionChain, ClientError> {
+    let mut chain = SuccessionChain::new();
+    let mut next = Some(name.clone());
+    while let Some(name) = next {
+      let record = self.resolve_record(&name).await?;
+      let revision = record.to_revision(&name).change_context(ClientError)?;
+      next = chain.push(revision).change_context(ClientError)?;
+    }
+    Ok(chain)
+  }
+
+  /// Fetches and verifies the latest signed [IpnsRecord] for the given [Name].
+  ///
+  /// Unlike [resolve](Self::resolve), this keeps the signatures, TTL and other record fields,
+  /// so the record can be archived or forwarded.
+  pub async fn resolve_record(&self, name: &Name) -> Result<IpnsRecord, ClientError> {
+    let record = self.fetch_record(name).await?;
+    record
+      .verify_with_options(name, &self.validation)
+      .change_context(ClientError)?;
+    Ok(record)
+  }
+
+  /// Fetches the latest [IpnsRecord] for the given [Name] **without** verifying it.
+  ///
+  /// This is meant for inspecting records that fail verification, e.g. with [IpnsRecord::verification_report].
+  /// Use [resolve_record](Self::resolve_record) to get a record you can trust.
+  pub async fn fetch_record(&self, name: &Name) -> Result<IpnsRecord, ClientError> {
     let mut url = self.endpoint.clone();
-    url.set_path(format!("name/{}", name.to_string()).as_str());
+    url.set_path(format!("name/{name}").as_str());
 
     self.limiter.until_ready().await;
     let res = self
@@ -78,7 +247,7 @@ impl W3NameClient {
       .change_context(ClientError)?;
 
     if res.status().is_success() {
-      parse_resolve_response(&name, res).await
+      parse_resolve_response(res).await
     } else {
       Err(error_from_response(res).await)
     }
@@ -92,20 +261,22 @@ impl Default for W3NameClient {
   }
 }
 
-async fn parse_resolve_response(name: &Name, res: Response) -> Result<Revision, ClientError> {
-  let r = res
-    .json::<ResolveResponse>()
+/// Returns true if the error report contains an [APIError] with a 404 status.
+fn is_not_found(report: &Report<ClientError>) -> bool {
+  matches!(report.downcast_ref::<APIError>(), Some(err) if err.status_code == 404)
+}
+
+async fn parse_resolve_response(res: Response) -> Result<IpnsRecord, ClientError> {
+  let body = read_body(res, MAX_RESPONSE_SIZE)
     .await
+    .change_context(ClientError)?;
+  let r: ResolveResponse = serde_json::from_slice(&body)
     .report()
     .change_context(ClientError)?;
   let entry_bytes = base64::decode(r.record)
     .report()
     .change_context(ClientError)?;
-  let entry = deserialize_ipns_entry(&entry_bytes).change_context(ClientError)?;
-  validate_ipns_entry(&entry, name.public_key()).change_context(ClientError)?;
-
-  let revision = revision_from_ipns_entry(&entry, name).change_context(ClientError)?;
-  Ok(revision)
+  IpnsRecord::decode(&entry_bytes).change_context(ClientError)
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -118,12 +289,113 @@ struct ResolveResponse {
   record: String,
 }
 
+fn check_record_size(encoded: &[u8]) -> Result<(), ClientError> {
+  if encoded.len() > MAX_RECORD_SIZE {
+    return Err(
+      report!(RecordTooLarge {
+        size: encoded.len(),
+        limit: MAX_RECORD_SIZE
+      })
+      .change_context(ClientError),
+    );
+  }
+  Ok(())
+}
+
+/// Reads the body of a response, failing with a [ResponseTooLarge] error if it's larger than `limit` bytes.
+///
+/// This keeps a misbehaving endpoint from making us buffer an arbitrarily large body.
+async fn read_body(mut res: Response, limit: usize) -> Result<Vec<u8>, HttpError> {
+  if matches!(res.content_length(), Some(len) if len > limit as u64) {
+    return Err(report!(ResponseTooLarge { limit }).change_context(HttpError));
+  }
+
+  let mut body = Vec::new();
+  while let Some(chunk) = res.chunk().await.report().change_context(HttpError)? {
+    if body.len() + chunk.len() > limit {
+      return Err(report!(ResponseTooLarge { limit }).change_context(HttpError));
+    }
+    body.extend_from_slice(&chunk);
+  }
+  Ok(body)
+}
+
 async fn error_from_response(res: Response) -> Report<ClientError> {
   let status = res.status();
-  match res.json::<APIErrorResponse>().await {
-    Ok(json) => report!(APIError{ message: json.message, status_code: status }).change_context(ClientError),
+  let body = match read_body(res, MAX_RESPONSE_SIZE).await {
+    Ok(body) => body,
+    Err(e) => {
+      return e
+        .change_context(UnexpectedAPIResponse)
+        .change_context(ClientError)
+    }
+  };
+  match serde_json::from_slice::<APIErrorResponse>(&body) {
+    Ok(json) => report!(APIError {
+      message: json.message,
+      status_code: status
+    })
+    .change_context(ClientError),
     Err(e) => report!(e)
       .change_context(UnexpectedAPIResponse)
       .change_context(ClientError),
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+  };
+
+  /// Serves a single HTTP response with the given extra headers and body, and returns its URL.
+  async fn serve_once(headers: String, body: Vec<u8>) -> Url {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+    tokio::spawn(async move {
+      let (mut socket, _) = listener.accept().await.unwrap();
+      let mut request = [0; 4096];
+      let _ = socket.read(&mut request).await;
+      let head = format!("HTTP/1.1 200 OK\r\n{headers}connection: close\r\n\r\n");
+      // the client hangs up once the body is too large, so writes may fail
+      let _ = socket.write_all(head.as_bytes()).await;
+      let _ = socket.write_all(&body).await;
+    });
+    url
+  }
+
+  async fn fetch(headers: String, body: Vec<u8>) -> Response {
+    let url = serve_once(headers, body).await;
+    Client::new().get(url).send().await.unwrap()
+  }
+
+  #[tokio::test]
+  async fn read_body_enforces_the_limit() {
+    let body = vec![b'a'; 100];
+    let res = fetch(format!("content-length: {}\r\n", body.len()), body.clone()).await;
+    assert_eq!(read_body(res, 100).await.unwrap(), body);
+
+    // a declared length over the limit is rejected before reading the body
+    let res = fetch("content-length: 1000000\r\n".to_string(), vec![]).await;
+    let err = read_body(res, 100).await.unwrap_err();
+    assert_eq!(err.downcast_ref::<ResponseTooLarge>().unwrap().limit, 100);
+
+    // without a length, reading stops once the limit is passed
+    let res = fetch(String::new(), vec![b'a'; 101]).await;
+    assert!(res.content_length().is_none());
+    let err = read_body(res, 100).await.unwrap_err();
+    assert!(err.downcast_ref::<ResponseTooLarge>().is_some());
+  }
+
+  #[test]
+  fn rejects_oversized_records() {
+    check_record_size(&[0; MAX_RECORD_SIZE]).unwrap();
+    let err = check_record_size(&[0; MAX_RECORD_SIZE + 1]).unwrap_err();
+    assert_eq!(
+      err.downcast_ref::<RecordTooLarge>().unwrap().size,
+      MAX_RECORD_SIZE + 1
+    );
+  }
+}
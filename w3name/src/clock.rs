@@ -0,0 +1,61 @@
+use std::fmt::Debug;
+
+use chrono::{DateTime, Utc};
+
+/// A `Clock` provides the current time wherever this crate needs it: for the default validity of new
+/// [Revision](crate::Revision)s, and for the expiry check in [ValidationOptions](crate::ValidationOptions).
+///
+/// The [SystemClock] is used by default. A [FixedClock] always returns the same time, which makes
+/// record bytes reproducible and time-dependent behavior easy to test.
+///
+/// ## Example
+///
+/// ```rust
+/// # fn main() -> error_stack::Result<(), w3name::error::IpnsError> {
+/// use chrono::{TimeZone, Utc};
+/// use w3name::{FixedClock, IpnsRecord, Revision, WritableName};
+///
+/// let clock = FixedClock::new(Utc.ymd(2022, 9, 23).and_hms(0, 0, 0));
+/// let name = WritableName::new();
+///
+/// let rev = Revision::v0_with_clock(&name.to_name(), "a value", &clock);
+/// assert_eq!(rev.validity(), &Utc.ymd(2023, 9, 22).and_hms(0, 0, 0));
+///
+/// // signing the same revision with the same key gives identical bytes
+/// let a = IpnsRecord::new(&name, &rev)?.encode()?;
+/// let b = IpnsRecord::new(&name, &Revision::v0_with_clock(&name.to_name(), "a value", &clock))?.encode()?;
+/// assert_eq!(a, b);
+/// # Ok(())
+/// # }
+/// ```
+pub trait Clock: Debug + Send + Sync {
+  /// Returns the current time.
+  fn now(&self) -> DateTime<Utc>;
+}
+
+/// A [Clock] that returns the current system time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+  fn now(&self) -> DateTime<Utc> {
+    Utc::now()
+  }
+}
+
+/// A [Clock] that always returns the same time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FixedClock(DateTime<Utc>);
+
+impl FixedClock {
+  /// Creates a `FixedClock` that always returns `now`.
+  pub fn new(now: DateTime<Utc>) -> FixedClock {
+    FixedClock(now)
+  }
+}
+
+impl Clock for FixedClock {
+  fn now(&self) -> DateTime<Utc> {
+    self.0
+  }
+}
@@ -0,0 +1,226 @@
+use std::fmt::Debug;
+
+use bip39::Mnemonic;
+use error_stack::{report, Result, ResultExt};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use libp2p_core::identity::{ed25519, Keypair};
+use sha2::{Sha256, Sha512};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::{error::KeyDerivationError, WritableName};
+
+const HARDENED_OFFSET: u32 = 1 << 31;
+
+/// A `MasterKey` deterministically derives any number of ed25519 [WritableName]s from a single secret.
+///
+/// Keys are derived following [SLIP-0010](https://github.com/satoshilabs/slips/blob/master/slip-0010.md)
+/// for ed25519, so a `MasterKey` created from a BIP-39 mnemonic gives the same keys as other SLIP-0010 wallets.
+/// Names can be derived either from a path like `m/0'/1'`, or from a free-form label like `"blog"`.
+///
+/// ## Example
+///
+/// ```rust
+/// # fn main() -> error_stack::Result<(), w3name::error::KeyDerivationError> {
+/// use w3name::MasterKey;
+///
+/// let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+/// let master = MasterKey::from_mnemonic(phrase, "")?;
+///
+/// // the same secret and path always give the same name
+/// let blog = master.derive_label("blog");
+/// assert_eq!(blog, MasterKey::from_mnemonic(phrase, "")?.derive_label("blog"));
+///
+/// let first = master.derive_path("m/0'")?.to_writable_name();
+/// assert_ne!(first, blog);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct MasterKey {
+  key: [u8; 32],
+  chain_code: [u8; 32],
+}
+
+impl MasterKey {
+  /// Creates a `MasterKey` from a seed of 16 to 64 bytes, such as the output of a BIP-39 mnemonic.
+  pub fn from_seed(seed: &[u8]) -> Result<MasterKey, KeyDerivationError> {
+    if !(16..=64).contains(&seed.len()) {
+      return Err(report!(KeyDerivationError).attach_printable(format!(
+        "seed must be between 16 and 64 bytes, got {}",
+        seed.len()
+      )));
+    }
+    Ok(MasterKey::from_hmac(b"ed25519 seed", seed))
+  }
+
+  /// Creates a `MasterKey` from a [BIP-39](https://github.com/bitcoin/bips/blob/master/bip-0039.mediawiki)
+  /// mnemonic phrase in English, and an optional passphrase (use `""` for none).
+  pub fn from_mnemonic(phrase: &str, passphrase: &str) -> Result<MasterKey, KeyDerivationError> {
+    let mnemonic = Mnemonic::parse(phrase).map_err(|e| {
+      report!(KeyDerivationError).attach_printable(format!("invalid mnemonic: {e}"))
+    })?;
+    let seed = Zeroizing::new(mnemonic.to_seed(passphrase));
+    MasterKey::from_seed(&*seed)
+  }
+
+  /// Derives the child key at the given path, e.g. `m/44'/0'/1'`.
+  ///
+  /// SLIP-0010 only defines hardened derivation for ed25519 keys, so every index in the path must be
+  /// hardened, which is marked with a trailing `'` or `h`. The leading `m` is optional.
+  pub fn derive_path(&self, path: &str) -> Result<MasterKey, KeyDerivationError> {
+    let path = path.strip_prefix('m').unwrap_or(path);
+    let path = path.strip_prefix('/').unwrap_or(path);
+    if path.is_empty() {
+      return Ok(self.clone());
+    }
+
+    path.split('/').try_fold(self.clone(), |key, segment| {
+      let index = parse_hardened_index(segment).attach_printable_lazy(|| {
+        format!("invalid path segment {segment:?}: expected a hardened index like 0'")
+      })?;
+      Ok(key.derive_child(index))
+    })
+  }
+
+  /// Derives a [WritableName] for a free-form label, such as the name of a site or deployment.
+  ///
+  /// Different labels give unrelated keys, and the labels can't be recovered from the keys.
+  pub fn derive_label(&self, label: &str) -> WritableName {
+    let hkdf = Hkdf::<Sha256>::new(Some(&self.chain_code), &self.key);
+    let mut seed = Zeroizing::new([0u8; 32]);
+    hkdf
+      .expand(
+        &[b"w3name label:".as_slice(), label.as_bytes()].concat(),
+        &mut *seed,
+      )
+      .expect("32 bytes is a valid length for HKDF-SHA256 output");
+    WritableName::from_seed(*seed)
+  }
+
+  /// Returns a [WritableName] for the ed25519 key at this point in the derivation tree.
+  pub fn to_writable_name(&self) -> WritableName {
+    WritableName::from_seed(self.key)
+  }
+
+  fn derive_child(&self, index: u32) -> MasterKey {
+    let data = Zeroizing::new(
+      [
+        &[0u8][..],
+        &self.key,
+        &(index | HARDENED_OFFSET).to_be_bytes(),
+      ]
+      .concat(),
+    );
+    MasterKey::from_hmac(&self.chain_code, &data)
+  }
+
+  fn from_hmac(key: &[u8], data: &[u8]) -> MasterKey {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    let mut output = mac.finalize().into_bytes();
+
+    let mut master = MasterKey {
+      key: [0; 32],
+      chain_code: [0; 32],
+    };
+    master.key.copy_from_slice(&output[..32]);
+    master.chain_code.copy_from_slice(&output[32..]);
+    output.as_mut_slice().zeroize();
+    master
+  }
+}
+
+impl Drop for MasterKey {
+  fn drop(&mut self) {
+    self.key.zeroize();
+    self.chain_code.zeroize();
+  }
+}
+
+// Don't leak the secret key material into logs.
+impl Debug for MasterKey {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("MasterKey").finish_non_exhaustive()
+  }
+}
+
+fn parse_hardened_index(segment: &str) -> Result<u32, KeyDerivationError> {
+  let digits = segment
+    .strip_suffix(['\'', 'h', 'H'])
+    .ok_or_else(|| report!(KeyDerivationError))?;
+  let index: u32 = digits.parse().map_err(|_| report!(KeyDerivationError))?;
+  if index >= HARDENED_OFFSET {
+    return Err(report!(KeyDerivationError));
+  }
+  Ok(index)
+}
+
+/// Returns the ed25519 keypair whose 32-byte private key is `seed`. The seed is zeroized.
+pub(crate) fn ed25519_from_seed(mut seed: [u8; 32]) -> Keypair {
+  let secret =
+    ed25519::SecretKey::from_bytes(&mut seed).expect("any 32 bytes are a valid ed25519 secret");
+  Keypair::Ed25519(secret.into())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+  }
+
+  #[test]
+  fn slip10_test_vector() {
+    // SLIP-0010 ed25519 test vector 1
+    let seed: Vec<u8> = (0u8..16).collect();
+    let master = MasterKey::from_seed(&seed).unwrap();
+    assert_eq!(
+      hex(&master.key),
+      "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7"
+    );
+    assert_eq!(
+      hex(&master.chain_code),
+      "90046a93de5380a72b5e45010748567d5ea02bbf6522f979e05c0d8d8ca9fffb"
+    );
+
+    let child = master.derive_path("m/0'").unwrap();
+    assert_eq!(
+      hex(&child.key),
+      "68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3"
+    );
+    assert_eq!(
+      master.derive_path("m/0h/1H").unwrap(),
+      child.derive_path("1'").unwrap()
+    );
+    assert_eq!(master.derive_path("m").unwrap(), master);
+  }
+
+  #[test]
+  fn rejects_invalid_input() {
+    assert!(MasterKey::from_seed(&[0; 15]).is_err());
+    assert!(MasterKey::from_seed(&[0; 65]).is_err());
+    assert!(MasterKey::from_mnemonic("not a valid mnemonic", "").is_err());
+
+    let master = MasterKey::from_seed(&[7; 32]).unwrap();
+    assert!(master.derive_path("m/0").is_err());
+    assert!(master.derive_path("m/x'").is_err());
+    assert!(master.derive_path("m/2147483648'").is_err());
+  }
+
+  #[test]
+  fn labels_and_passphrases_give_distinct_names() {
+    let phrase = "legal winner thank year wave sausage worth useful legal winner thank yellow";
+    let master = MasterKey::from_mnemonic(phrase, "").unwrap();
+
+    assert_eq!(master.derive_label("a"), master.derive_label("a"));
+    assert_ne!(master.derive_label("a"), master.derive_label("b"));
+    assert_ne!(
+      master.to_writable_name(),
+      MasterKey::from_mnemonic(phrase, "TREZOR")
+        .unwrap()
+        .to_writable_name()
+    );
+  }
+}
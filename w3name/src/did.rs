@@ -0,0 +1,92 @@
+//! Conversion between public keys and [did:key](https://w3c-ccg.github.io/did-method-key/) identifiers.
+//!
+//! A `did:key` is `did:key:z` followed by the base58btc encoding of a multicodec-prefixed public key.
+//! Keys are stored in their compressed form, as the spec requires.
+
+use error_stack::{report, Result};
+use libp2p_core::identity::{ecdsa, ed25519, secp256k1, PublicKey};
+use multibase::Base;
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+
+use crate::error::InvalidDidKey;
+
+const DID_KEY_PREFIX: &str = "did:key:";
+
+// varint-encoded multicodec codes for the supported key types
+const ED25519_PUB: [u8; 2] = [0xed, 0x01];
+const SECP256K1_PUB: [u8; 2] = [0xe7, 0x01];
+const P256_PUB: [u8; 2] = [0x80, 0x24];
+
+/// Encodes a public key as a `did:key` identifier. RSA keys are not supported.
+pub fn encode_did_key(public_key: &PublicKey) -> Result<String, InvalidDidKey> {
+  let (codec, key_bytes) = match public_key {
+    PublicKey::Ed25519(pk) => (ED25519_PUB, pk.encode().to_vec()),
+    PublicKey::Secp256k1(pk) => (SECP256K1_PUB, pk.encode().to_vec()),
+    PublicKey::Ecdsa(pk) => {
+      let point = p256::PublicKey::from_sec1_bytes(&pk.to_bytes())
+        .map_err(|_| report!(InvalidDidKey).attach_printable("invalid ECDSA public key"))?;
+      (P256_PUB, point.to_encoded_point(true).as_bytes().to_vec())
+    }
+    #[cfg(feature = "rsa")]
+    PublicKey::Rsa(_) => {
+      return Err(report!(InvalidDidKey).attach_printable("RSA keys are not supported"))
+    }
+  };
+
+  let mut bytes = codec.to_vec();
+  bytes.extend(key_bytes);
+  Ok(format!(
+    "{DID_KEY_PREFIX}{}",
+    multibase::encode(Base::Base58Btc, bytes)
+  ))
+}
+
+/// Decodes the public key from a `did:key` identifier.
+pub fn decode_did_key(did: &str) -> Result<PublicKey, InvalidDidKey> {
+  let encoded = did.strip_prefix(DID_KEY_PREFIX).ok_or_else(|| {
+    report!(InvalidDidKey).attach_printable(format!("missing {DID_KEY_PREFIX} prefix"))
+  })?;
+  let bytes = match multibase::decode(encoded) {
+    Ok((Base::Base58Btc, bytes)) if bytes.len() > 2 => bytes,
+    _ => {
+      return Err(report!(InvalidDidKey).attach_printable("expected a base58btc multibase string"))
+    }
+  };
+
+  let (codec, key_bytes) = bytes.split_at(2);
+  let public_key = match [codec[0], codec[1]] {
+    ED25519_PUB => ed25519::PublicKey::decode(key_bytes).map(PublicKey::Ed25519),
+    SECP256K1_PUB => secp256k1::PublicKey::decode(key_bytes).map(PublicKey::Secp256k1),
+    P256_PUB => ecdsa::PublicKey::from_bytes(key_bytes).map(PublicKey::Ecdsa),
+    _ => {
+      return Err(report!(InvalidDidKey).attach_printable("unsupported key type"));
+    }
+  };
+  public_key.map_err(|e| report!(InvalidDidKey).attach_printable(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn spec_test_vectors() {
+    // from the did:key spec test vectors
+    for did in [
+      "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp",
+      "did:key:zQ3shokFTS3brHcDQrn82RUDfCZESWL1ZdCEJwekUDPQiYBme",
+      "did:key:zDnaerDaTF5BXEavCrfRZEk316dpbLsfPDZ3WJ5hRTPFU2169",
+    ] {
+      let public_key = decode_did_key(did).unwrap();
+      assert_eq!(encode_did_key(&public_key).unwrap(), did);
+    }
+  }
+
+  #[test]
+  fn rejects_invalid_did_keys() {
+    assert!(decode_did_key("did:web:example.com").is_err());
+    assert!(decode_did_key("did:key:6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp").is_err());
+    // an ed25519 prefix with too few key bytes
+    assert!(decode_did_key("did:key:z6Mk").is_err());
+  }
+}
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use std::error::Error;
 use std::fmt::Display;
 
@@ -24,7 +25,7 @@ impl Display for HttpError {
 impl Error for HttpError {}
 
 #[derive(Debug)]
-pub struct APIError { 
+pub struct APIError {
   pub message: String,
   pub status_code: reqwest::StatusCode,
 }
@@ -51,6 +52,19 @@ impl Display for UnexpectedAPIResponse {
 
 impl Error for UnexpectedAPIResponse {}
 
+#[derive(Debug)]
+pub struct ResponseTooLarge {
+  pub limit: usize,
+}
+
+impl Display for ResponseTooLarge {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "response body exceeds the limit of {} bytes", self.limit)
+  }
+}
+
+impl Error for ResponseTooLarge {}
+
 #[derive(Debug)]
 pub struct NameError;
 
@@ -95,6 +109,99 @@ impl Display for InvalidCryptoKey {
 
 impl Error for InvalidCryptoKey {}
 
+#[derive(Debug)]
+pub struct KeyFormatError;
+
+impl Display for KeyFormatError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "invalid or unsupported key encoding")
+  }
+}
+
+impl Error for KeyFormatError {}
+
+#[derive(Debug)]
+pub struct KeyFileError;
+
+impl Display for KeyFileError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "unable to read or write encrypted key file")
+  }
+}
+
+impl Error for KeyFileError {}
+
+#[derive(Debug)]
+pub struct KeyDerivationError;
+
+impl Display for KeyDerivationError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "key derivation error")
+  }
+}
+
+impl Error for KeyDerivationError {}
+
+#[derive(Debug)]
+pub struct InvalidDidKey;
+
+impl Display for InvalidDidKey {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "invalid did:key")
+  }
+}
+
+impl Error for InvalidDidKey {}
+
+#[derive(Debug)]
+pub struct KeystoreError;
+
+impl Display for KeystoreError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "keystore error")
+  }
+}
+
+impl Error for KeystoreError {}
+
+#[derive(Debug)]
+pub struct KeyNotFound {
+  pub key: String,
+}
+
+impl Display for KeyNotFound {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "no key found for {}", self.key)
+  }
+}
+
+impl Error for KeyNotFound {}
+
+#[derive(Debug)]
+pub struct MissingPublicKey;
+
+impl Display for MissingPublicKey {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "IPNS record has no public key, and the name doesn't embed one"
+    )
+  }
+}
+
+impl Error for MissingPublicKey {}
+
+#[derive(Debug)]
+pub struct PublicKeyMismatch;
+
+impl Display for PublicKeyMismatch {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "public key in IPNS record does not match the name")
+  }
+}
+
+impl Error for PublicKeyMismatch {}
+
 #[derive(Debug)]
 pub struct SigningError;
 
@@ -128,6 +235,42 @@ impl Display for ProtobufError {
 
 impl Error for ProtobufError {}
 
+#[derive(Debug)]
+pub struct RecordTooLarge {
+  pub size: usize,
+  pub limit: usize,
+}
+
+impl Display for RecordTooLarge {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "IPNS record is {} bytes, which exceeds the limit of {} bytes",
+      self.size, self.limit
+    )
+  }
+}
+
+impl Error for RecordTooLarge {}
+
+#[derive(Debug)]
+pub struct ReceivedRecordTooLarge {
+  pub size: usize,
+  pub limit: usize,
+}
+
+impl Display for ReceivedRecordTooLarge {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "received IPNS record is {} bytes, which exceeds the limit of {} bytes",
+      self.size, self.limit
+    )
+  }
+}
+
+impl Error for ReceivedRecordTooLarge {}
+
 #[derive(Debug)]
 pub struct InvalidIpnsV1Signature;
 
@@ -150,6 +293,17 @@ impl Display for InvalidIpnsV2Signature {
 
 impl Error for InvalidIpnsV2Signature {}
 
+#[derive(Debug)]
+pub struct MissingIpnsV2Signature;
+
+impl Display for MissingIpnsV2Signature {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "IPNS record has no v2 signature")
+  }
+}
+
+impl Error for MissingIpnsV2Signature {}
+
 #[derive(Debug)]
 pub struct InvalidIpnsV2SignatureData;
 
@@ -175,6 +329,108 @@ impl Display for InvalidUtf8 {
 
 impl Error for InvalidUtf8 {}
 
+#[derive(Debug)]
+pub struct InvalidValue;
+
+impl Display for InvalidValue {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "invalid record value")
+  }
+}
+
+impl Error for InvalidValue {}
+
+#[derive(Debug)]
+pub struct InvalidExtensionField;
+
+impl Display for InvalidExtensionField {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "invalid extension field")
+  }
+}
+
+impl Error for InvalidExtensionField {}
+
+#[derive(Debug)]
+pub struct InvalidSuccession;
+
+impl Display for InvalidSuccession {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "invalid name succession")
+  }
+}
+
+impl Error for InvalidSuccession {}
+
+#[derive(Debug)]
+pub struct ResolutionError;
+
+impl Display for ResolutionError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "unable to resolve name recursively")
+  }
+}
+
+impl Error for ResolutionError {}
+
+#[derive(Debug)]
+pub struct ResolutionCycle {
+  pub name: String,
+}
+
+impl Display for ResolutionCycle {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "name {} points back to itself", self.name)
+  }
+}
+
+impl Error for ResolutionCycle {}
+
+#[derive(Debug)]
+pub struct ResolutionDepthExceeded {
+  pub limit: usize,
+}
+
+impl Display for ResolutionDepthExceeded {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "resolution needs more than the maximum of {} names",
+      self.limit
+    )
+  }
+}
+
+impl Error for ResolutionDepthExceeded {}
+
+#[derive(Debug)]
+pub struct VanityError;
+
+impl Display for VanityError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "vanity name search failed")
+  }
+}
+
+impl Error for VanityError {}
+
+#[derive(Debug)]
+pub struct VanitySearchCancelled {
+  pub attempts: u64,
+}
+
+impl Display for VanitySearchCancelled {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "vanity name search cancelled after {} keys",
+      self.attempts
+    )
+  }
+}
+
+impl Error for VanitySearchCancelled {}
+
 #[derive(Debug)]
 pub struct InvalidDateString;
 
@@ -184,6 +440,21 @@ impl Display for InvalidDateString {
   }
 }
 
+impl Error for InvalidDateString {}
+
+#[derive(Debug)]
+pub struct RecordExpired {
+  pub expired_at: DateTime<Utc>,
+}
+
+impl Display for RecordExpired {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "IPNS record expired at {}", self.expired_at.to_rfc3339())
+  }
+}
+
+impl Error for RecordExpired {}
+
 #[derive(Debug)]
 pub struct IpnsError;
 
@@ -1,9 +1,14 @@
 use multihash::derive::Multihash;
 
-/// Hasher is a custom Multihash "code table" with just the identity hash function enabled.
+/// Hasher is a custom Multihash "code table" with just the hash functions used for name identifiers.
+///
+/// Public keys that are small enough are embedded in the name with the identity hash,
+/// and larger keys (e.g. RSA and ECDSA) are hashed with sha2-256.
 #[derive(Clone, Copy, Debug, Eq, Multihash, PartialEq)]
 #[mh(alloc_size = 64)]
 pub enum Hasher {
   #[mh(code = 0x0, hasher = multihash::IdentityHasher::<64>)]
   Identity,
+  #[mh(code = 0x12, hasher = multihash::Sha2_256)]
+  Sha2_256,
 }
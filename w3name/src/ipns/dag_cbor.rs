@@ -0,0 +1,117 @@
+//! Deterministic [DAG-CBOR](https://ipld.io/specs/codecs/dag-cbor/spec/) encoding for the IPNS v2 signature data.
+//!
+//! The v2 signature covers the CBOR bytes in the record's `data` field, so every implementation has to
+//! produce exactly the same bytes for the same fields. DAG-CBOR makes the encoding deterministic by
+//! requiring map keys to be sorted by length and then bytewise, integers to use the shortest form,
+//! and floats to always use 64 bits.
+
+use std::collections::BTreeMap;
+
+use error_stack::{report, IntoReport, Result, ResultExt};
+use serde_cbor::Value;
+
+use crate::error::CborError;
+
+/// Encodes a map with string keys as DAG-CBOR.
+///
+/// Floats and CBOR tags are not supported, since IPNS records don't use them.
+pub fn encode_map(map: &BTreeMap<String, Value>) -> Result<Vec<u8>, CborError> {
+  for value in map.values() {
+    check_supported(value)?;
+  }
+
+  // serde_cbor orders `Value` map keys canonically (shorter keys first, then bytewise),
+  // which is the key order required by DAG-CBOR.
+  let canonical: BTreeMap<Value, Value> = map
+    .iter()
+    .map(|(k, v)| (Value::Text(k.clone()), v.clone()))
+    .collect();
+  serde_cbor::to_vec(&Value::Map(canonical))
+    .report()
+    .change_context(CborError)
+}
+
+/// Decodes a CBOR map with string keys.
+///
+/// Keys are accepted in any order, since records signed by older implementations (including older
+/// versions of this crate) don't always sort them. Use [is_canonical] to check whether the bytes
+/// are in the exact form [encode_map] would produce.
+pub fn decode_map(bytes: &[u8]) -> Result<BTreeMap<String, Value>, CborError> {
+  let value: Value = serde_cbor::from_slice(bytes)
+    .report()
+    .change_context(CborError)?;
+  let map = match value {
+    Value::Map(map) => map,
+    _ => return Err(report!(CborError).attach_printable("expected a CBOR map")),
+  };
+
+  map
+    .into_iter()
+    .map(|(k, v)| match k {
+      Value::Text(k) => Ok((k, v)),
+      _ => Err(report!(CborError).attach_printable("map keys must be strings")),
+    })
+    .collect()
+}
+
+/// Returns true if `bytes` is a map in the canonical DAG-CBOR form produced by [encode_map].
+pub fn is_canonical(bytes: &[u8]) -> bool {
+  match decode_map(bytes).and_then(|map| encode_map(&map)) {
+    Ok(encoded) => encoded == bytes,
+    Err(_) => false,
+  }
+}
+
+/// Checks that a value can be encoded as DAG-CBOR by [encode_map].
+pub fn check_supported(value: &Value) -> Result<(), CborError> {
+  match value {
+    Value::Float(_) => Err(report!(CborError).attach_printable("floats are not supported")),
+    Value::Integer(i) if *i < i64::MIN as i128 || *i > u64::MAX as i128 => {
+      Err(report!(CborError).attach_printable("integer out of range"))
+    }
+    Value::Array(values) => values.iter().try_for_each(check_supported),
+    Value::Map(map) => map.iter().try_for_each(|(k, v)| {
+      check_supported(k)?;
+      check_supported(v)
+    }),
+    Value::Null | Value::Bool(_) | Value::Integer(_) | Value::Bytes(_) | Value::Text(_) => Ok(()),
+    _ => Err(report!(CborError).attach_printable("unsupported CBOR value")),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn sorts_keys_by_length_then_bytes() {
+    let mut map = BTreeMap::new();
+    map.insert("bb".to_string(), Value::Integer(1));
+    map.insert("a".to_string(), Value::Integer(2));
+    map.insert("ab".to_string(), Value::Integer(3));
+
+    let encoded = encode_map(&map).unwrap();
+    assert_eq!(
+      encoded,
+      vec![0xa3, 0x61, b'a', 0x02, 0x62, b'a', b'b', 0x03, 0x62, b'b', b'b', 0x01]
+    );
+    assert!(is_canonical(&encoded));
+    assert_eq!(decode_map(&encoded).unwrap(), map);
+  }
+
+  #[test]
+  fn decodes_unsorted_maps() {
+    // {"bb": 1, "a": 2}, with keys in the wrong order
+    let unsorted = vec![0xa2, 0x62, b'b', b'b', 0x01, 0x61, b'a', 0x02];
+    let map = decode_map(&unsorted).unwrap();
+    assert_eq!(map.get("a"), Some(&Value::Integer(2)));
+    assert!(!is_canonical(&unsorted));
+  }
+
+  #[test]
+  fn rejects_floats() {
+    let mut map = BTreeMap::new();
+    map.insert("f".to_string(), Value::Float(1.5));
+    assert!(encode_map(&map).is_err());
+  }
+}
@@ -1,117 +1,345 @@
 use crate::{
   error::{
-    CborError, InvalidIpnsV1Signature, InvalidIpnsV2Signature, InvalidIpnsV2SignatureData,
-    IpnsError, SigningError,
+    CborError, InvalidCryptoKey, InvalidDateString, InvalidIpnsV1Signature, InvalidIpnsV2Signature,
+    InvalidIpnsV2SignatureData, IpnsError, MissingIpnsV2Signature, MissingPublicKey,
+    PublicKeyMismatch, ReceivedRecordTooLarge, RecordExpired, RecordTooLarge, SigningError,
   },
   ipns_pb::IpnsEntry,
-  Name, Revision,
+  revision::ttl_nanos,
+  Name, Revision, Signer,
 };
 use chrono::{DateTime, Utc};
 use libp2p_core::identity::{Keypair, PublicKey};
 use prost::Message;
-use std::str::from_utf8;
+use serde_cbor::Value as CborValue;
+use std::{cmp::Ordering, collections::BTreeMap, str::from_utf8, time::Duration};
 
 use error_stack::{report, IntoReport, Result, ResultExt};
 
+mod dag_cbor;
+mod options;
+mod record;
+mod report;
+
+pub use options::{SigningOptions, ValidationOptions};
+pub use record::IpnsRecord;
+pub use report::{
+  CheckKind, CheckOutcome, RecordField, SignatureVersion, VerificationCheck, VerificationReport,
+};
+
+/// The maximum size of an encoded IPNS record in bytes, as defined by the
+/// [IPNS spec](https://specs.ipfs.tech/ipns/ipns-record/#record-size-limit).
+pub const MAX_RECORD_SIZE: usize = 10 * 1024;
+
 pub fn revision_to_ipns_entry(
   revision: &Revision,
   signer: &Keypair,
+  options: &SigningOptions,
 ) -> Result<IpnsEntry, IpnsError> {
-  let value = revision.value().as_bytes().to_vec();
-  let validity = revision.validity_string().as_bytes().to_vec();
-
-  let duration = revision.validity().signed_duration_since(Utc::now());
-  let ttl: u64 = duration.num_nanoseconds().unwrap_or(i64::MAX) as u64;
+  let unsigned = UnsignedEntry::new(revision)?;
+  let signature = if options.is_v2_only() {
+    vec![]
+  } else {
+    create_v1_signature(signer, &unsigned.value, &unsigned.validity).change_context(IpnsError)?
+  };
+  let signature_v2 = create_v2_signature(signer, &unsigned.data).change_context(IpnsError)?;
+  unsigned.into_entry(&signer.public(), signature, signature_v2)
+}
 
-  let signature = create_v1_signature(&signer, &value, &validity).change_context(IpnsError)?;
-  let data = v2_signature_data(
-    revision.value(),
-    &revision.validity_string(),
-    revision.sequence(),
-    ttl,
-  )
-  .change_context(IpnsError)?;
-  let signature_v2 = create_v2_signature(&signer, &data).change_context(IpnsError)?;
-  let entry = IpnsEntry {
-    value,
-    validity,
-    sequence: revision.sequence(),
-    validity_type: 0,
+/// Like [revision_to_ipns_entry], but signs with a [Signer], which may keep its key outside of this process.
+///
+/// Signatures from the signer are checked against its public key, so that a misconfigured signer fails here
+/// instead of producing a record that nobody will accept.
+pub async fn sign_ipns_entry<S: Signer + ?Sized>(
+  revision: &Revision,
+  signer: &S,
+  options: &SigningOptions,
+) -> Result<IpnsEntry, IpnsError> {
+  let unsigned = UnsignedEntry::new(revision)?;
+  let public_key = signer.public_key();
 
-    pub_key: vec![],
-    signature,
-    ttl,
-    signature_v2,
-    data,
+  let signature = if options.is_v2_only() {
+    vec![]
+  } else {
+    let msg = v1_signature_data(&unsigned.value, &unsigned.validity);
+    let sig = signer.sign(&msg).await.change_context(IpnsError)?;
+    check_signature(&public_key, &msg, &sig)?;
+    sig
   };
+  let msg = v2_signature_message(&unsigned.data);
+  let signature_v2 = signer.sign(&msg).await.change_context(IpnsError)?;
+  check_signature(&public_key, &msg, &signature_v2)?;
 
-  Ok(entry)
+  unsigned.into_entry(&public_key, signature, signature_v2)
+}
+
+fn check_signature(public_key: &PublicKey, msg: &[u8], sig: &[u8]) -> Result<(), IpnsError> {
+  if public_key.verify(msg, sig) {
+    Ok(())
+  } else {
+    Err(
+      report!(SigningError)
+        .attach_printable("the signer returned a signature that doesn't match its public key")
+        .change_context(IpnsError),
+    )
+  }
+}
+
+/// The fields of an [IpnsEntry] that are covered by its signatures.
+struct UnsignedEntry {
+  value: Vec<u8>,
+  validity: Vec<u8>,
+  sequence: u64,
+  ttl: u64,
+  data: Vec<u8>,
+}
+
+impl UnsignedEntry {
+  fn new(revision: &Revision) -> Result<UnsignedEntry, IpnsError> {
+    let ttl = ttl_nanos(revision.ttl());
+    let data = v2_signature_data(
+      revision.value(),
+      &revision.validity_string(),
+      revision.sequence(),
+      ttl,
+      revision.extensions(),
+    )
+    .change_context(IpnsError)?;
+
+    Ok(UnsignedEntry {
+      value: revision.value().to_vec(),
+      validity: revision.validity_string().as_bytes().to_vec(),
+      sequence: revision.sequence(),
+      ttl,
+      data,
+    })
+  }
+
+  fn into_entry(
+    self,
+    public_key: &PublicKey,
+    signature: Vec<u8>,
+    signature_v2: Vec<u8>,
+  ) -> Result<IpnsEntry, IpnsError> {
+    // keys that are too large to be embedded in the name have to be included in the record
+    let pub_key = if Name::from_public_key(public_key.clone()).embeds_public_key() {
+      vec![]
+    } else {
+      public_key.to_protobuf_encoding()
+    };
+
+    let entry = IpnsEntry {
+      value: self.value,
+      validity: self.validity,
+      sequence: self.sequence,
+      validity_type: 0,
+
+      pub_key,
+      signature,
+      ttl: self.ttl,
+      signature_v2,
+      data: self.data,
+    };
+
+    let size = entry.encoded_len();
+    if size > MAX_RECORD_SIZE {
+      return Err(
+        report!(RecordTooLarge {
+          size,
+          limit: MAX_RECORD_SIZE
+        })
+        .change_context(IpnsError),
+      );
+    }
+
+    Ok(entry)
+  }
 }
 
 pub fn serialize_ipns_entry(entry: &IpnsEntry) -> Result<Vec<u8>, IpnsError> {
-  let mut buf = Vec::new();
-  buf.reserve(entry.encoded_len());
+  let mut buf = Vec::with_capacity(entry.encoded_len());
   entry.encode(&mut buf).report().change_context(IpnsError)?;
   Ok(buf)
 }
 
 pub fn deserialize_ipns_entry(entry_bytes: &[u8]) -> Result<IpnsEntry, IpnsError> {
+  if entry_bytes.len() > MAX_RECORD_SIZE {
+    return Err(
+      report!(ReceivedRecordTooLarge {
+        size: entry_bytes.len(),
+        limit: MAX_RECORD_SIZE
+      })
+      .change_context(IpnsError),
+    );
+  }
+
   let entry = IpnsEntry::decode(entry_bytes)
     .report()
     .change_context(IpnsError)?;
   Ok(entry)
 }
 
-pub fn validate_ipns_entry(entry: &IpnsEntry, public_key: &PublicKey) -> Result<(), IpnsError> {
+pub fn validate_ipns_entry(
+  entry: &IpnsEntry,
+  name: &Name,
+  options: &ValidationOptions,
+) -> Result<(), IpnsError> {
+  let public_key = &entry_public_key(entry, name)?;
   if !entry.signature_v2.is_empty() && !entry.data.is_empty() {
     validate_v2_signature(public_key, &entry.signature_v2, &entry.data)
       .change_context(IpnsError)?;
     validate_v2_data_matches_entry_data(entry).change_context(IpnsError)?;
+  } else if options.requires_v2() {
+    return Err(report!(MissingIpnsV2Signature).change_context(IpnsError));
+  } else {
+    validate_v1_signature(entry, public_key).change_context(IpnsError)?;
+  }
 
-    return Ok(());
+  if !options.allows_expired() {
+    validate_not_expired(entry, options.now()).change_context(IpnsError)?;
   }
+  Ok(())
+}
+
+/// Returns the public key to verify an entry with, which is either embedded in the entry or in the name itself.
+///
+/// An embedded key must belong to the name, otherwise anyone could sign records for it. Errors carry a
+/// printable description of the failure, which [VerificationReport] shows as the reason.
+pub(super) fn entry_public_key(entry: &IpnsEntry, name: &Name) -> Result<PublicKey, IpnsError> {
+  if !entry.pub_key.is_empty() {
+    let public_key = PublicKey::from_protobuf_encoding(&entry.pub_key).map_err(|e| {
+      report!(InvalidCryptoKey)
+        .attach_printable(format!("the pub_key field is not a valid public key: {e}"))
+        .change_context(IpnsError)
+    })?;
+    if !name.matches_public_key(&public_key) {
+      return Err(
+        report!(PublicKeyMismatch)
+          .attach_printable(format!(
+            "the pub_key field holds a key that does not belong to {name}"
+          ))
+          .change_context(IpnsError),
+      );
+    }
+    return Ok(public_key);
+  }
+
+  name.public_key().cloned().ok_or_else(|| {
+    report!(MissingPublicKey)
+      .attach_printable(format!(
+        "the record has no pub_key field, and {name} does not embed its public key"
+      ))
+      .change_context(IpnsError)
+  })
+}
 
-  validate_v1_signature(entry, public_key).change_context(IpnsError)
+/// Compares two entries by the IPNS ordering rules: higher sequence, then later validity.
+///
+/// Entries with an unparseable validity lose to any entry with a valid one. Remaining ties are
+/// broken by comparing the protobuf encodings, which prost produces deterministically.
+pub fn compare_ipns_entries(a: &IpnsEntry, b: &IpnsEntry) -> Ordering {
+  a.sequence
+    .cmp(&b.sequence)
+    .then_with(|| {
+      let a_validity = parse_validity(&a.validity).ok();
+      let b_validity = parse_validity(&b.validity).ok();
+      a_validity.cmp(&b_validity)
+    })
+    .then_with(|| a.encode_to_vec().cmp(&b.encode_to_vec()))
 }
 
 pub fn revision_from_ipns_entry(entry: &IpnsEntry, name: &Name) -> Result<Revision, IpnsError> {
-  let value = from_utf8(&entry.value).report().change_context(IpnsError)?;
-  let validity_str = from_utf8(&entry.validity)
+  let value = &entry.value;
+  let validity = parse_validity(&entry.validity).change_context(IpnsError)?;
+
+  let ttl = Duration::from_nanos(entry.ttl);
+  let extensions = match signed_v2_data(entry, name).change_context(IpnsError)? {
+    Some(map) => {
+      SignatureV2Data::from_cbor_map(map)
+        .change_context(IpnsError)?
+        .extensions
+    }
+    None => BTreeMap::new(),
+  };
+
+  let rev = Revision::new(name, value, validity, entry.sequence, ttl).with_extensions(extensions);
+  Ok(rev)
+}
+
+/// Decodes the v2 data of an entry, but only if it has a v2 signature from the key of `name` that covers it.
+///
+/// The v1 signature doesn't cover `data`, so the fields of an entry without a valid v2 signature could have
+/// been added by anyone, and are ignored.
+pub fn signed_v2_data(
+  entry: &IpnsEntry,
+  name: &Name,
+) -> Result<Option<BTreeMap<String, CborValue>>, CborError> {
+  if entry.signature_v2.is_empty() || entry.data.is_empty() {
+    return Ok(None);
+  }
+  let verified = entry_public_key(entry, name)
+    .is_ok_and(|key| validate_v2_signature(&key, &entry.signature_v2, &entry.data).is_ok());
+  if !verified {
+    return Ok(None);
+  }
+  dag_cbor::decode_map(&entry.data).map(Some)
+}
+
+fn parse_validity(validity_bytes: &[u8]) -> Result<DateTime<Utc>, InvalidDateString> {
+  let validity_str = from_utf8(validity_bytes)
     .report()
-    .change_context(IpnsError)?;
+    .change_context(InvalidDateString)?;
   let validity = DateTime::parse_from_rfc3339(validity_str)
     .report()
-    .change_context(IpnsError)?;
+    .change_context(InvalidDateString)?;
+  Ok(validity.into())
+}
 
-  let rev = Revision::new(name, value, validity.into(), entry.sequence);
-  Ok(rev)
+fn validate_not_expired(entry: &IpnsEntry, now: DateTime<Utc>) -> Result<(), IpnsError> {
+  let expired_at = parse_validity(&entry.validity).change_context(IpnsError)?;
+  if now > expired_at {
+    Err(report!(RecordExpired { expired_at }).change_context(IpnsError))
+  } else {
+    Ok(())
+  }
 }
 
+/// Returns the message that v1 signatures are made over: the value, the validity and the validity type, in that order.
 fn v1_signature_data(value_bytes: &[u8], validity_bytes: &[u8]) -> Vec<u8> {
   let mut buf = value_bytes.to_vec();
-  buf.extend("EOL".as_bytes()); // validity type (we only support Eol)
   buf.extend(validity_bytes);
+  buf.extend("EOL".as_bytes()); // validity type (we only support Eol)
   buf
 }
 
+/// Returns true if `key` is one of the standard fields of the v2 signature data.
+pub fn is_reserved_field(key: &str) -> bool {
+  RESERVED_V2_FIELDS.contains(&key)
+}
+
+/// Checks that a value can be stored as an extension field in the v2 signature data.
+pub fn check_extension_field(value: &CborValue) -> Result<(), CborError> {
+  dag_cbor::check_supported(value)
+}
+
+const RESERVED_V2_FIELDS: [&str; 5] = ["Value", "Validity", "ValidityType", "Sequence", "TTL"];
+
 fn v2_signature_data(
-  value: &str,
+  value: &[u8],
   validity: &str,
   sequence: u64,
   ttl: u64,
+  extensions: &BTreeMap<String, CborValue>,
 ) -> Result<Vec<u8>, CborError> {
   let data = SignatureV2Data {
-    Value: value.as_bytes().to_vec(),
-    Validity: validity.as_bytes().to_vec(),
-    ValidityType: 0,
-    Sequence: sequence,
-    TTL: ttl,
+    value: value.to_vec(),
+    validity: validity.as_bytes().to_vec(),
+    validity_type: 0,
+    sequence,
+    ttl,
+    extensions: extensions.clone(),
   };
-  let encoded = serde_cbor::to_vec(&data)
-    .report()
-    .change_context(CborError)?;
-
-  Ok(encoded)
+  dag_cbor::encode_map(&data.to_cbor_map())
 }
 
 fn validate_v2_signature(
@@ -119,9 +347,7 @@ fn validate_v2_signature(
   sig: &[u8],
   data: &[u8],
 ) -> Result<(), InvalidIpnsV2Signature> {
-  let mut msg = "ipns-signature:".as_bytes().to_vec();
-  msg.extend_from_slice(data);
-  if public_key.verify(&msg, sig) {
+  if public_key.verify(&v2_signature_message(data), sig) {
     Ok(())
   } else {
     Err(report!(InvalidIpnsV2Signature))
@@ -135,21 +361,51 @@ fn validate_v2_data_matches_entry_data(
     return Err(report!(InvalidIpnsV2SignatureData));
   }
 
-  let data: SignatureV2Data = serde_cbor::from_slice(&entry.data[..])
-    .report()
+  let data = dag_cbor::decode_map(&entry.data)
+    .and_then(SignatureV2Data::from_cbor_map)
     .change_context(InvalidIpnsV2SignatureData)?;
-  if entry.value != data.Value
-    || entry.validity != data.Validity
-    || entry.sequence != data.Sequence
-    || entry.ttl != data.TTL
-    || entry.validity_type != data.ValidityType
-  {
-    Err(report!(InvalidIpnsV2SignatureData))
-  } else {
+  let mismatches = v2_data_mismatches(entry, &data);
+  if mismatches.is_empty() {
     Ok(())
+  } else {
+    Err(report!(InvalidIpnsV2SignatureData).attach_printable(mismatches.join("; ")))
   }
 }
 
+/// Describes each protobuf field of `entry` that differs from the signed v2 `data`.
+fn v2_data_mismatches(entry: &IpnsEntry, data: &SignatureV2Data) -> Vec<String> {
+  let mut mismatches = vec![];
+  let mut compare = |field: &str, record: String, signed: String| {
+    if record != signed {
+      mismatches.push(format!(
+        "{field} is {record} in the record, but {signed} in the signed data"
+      ));
+    }
+  };
+  compare(
+    "Value",
+    report::display_bytes(&entry.value),
+    report::display_bytes(&data.value),
+  );
+  compare(
+    "Validity",
+    report::display_bytes(&entry.validity),
+    report::display_bytes(&data.validity),
+  );
+  compare(
+    "ValidityType",
+    entry.validity_type.to_string(),
+    data.validity_type.to_string(),
+  );
+  compare(
+    "Sequence",
+    entry.sequence.to_string(),
+    data.sequence.to_string(),
+  );
+  compare("TTL", entry.ttl.to_string(), data.ttl.to_string());
+  mismatches
+}
+
 fn validate_v1_signature(
   entry: &IpnsEntry,
   public_key: &PublicKey,
@@ -173,45 +429,108 @@ fn create_v1_signature(
 }
 
 fn create_v2_signature(signer: &Keypair, sig_data: &[u8]) -> Result<Vec<u8>, SigningError> {
-  let mut msg = "ipns-signature:".as_bytes().to_vec();
-  msg.extend_from_slice(sig_data);
+  let msg = v2_signature_message(sig_data);
   let sig = signer.sign(&msg).report().change_context(SigningError)?;
   Ok(sig)
 }
 
-#[allow(non_snake_case)]
-#[derive(serde::Serialize, serde::Deserialize)]
+/// Returns the message that v2 signatures are made over: the v2 data with a fixed prefix.
+fn v2_signature_message(sig_data: &[u8]) -> Vec<u8> {
+  let mut msg = "ipns-signature:".as_bytes().to_vec();
+  msg.extend_from_slice(sig_data);
+  msg
+}
+
+/// The fields covered by the v2 signature, which are stored as a DAG-CBOR map in the record's `data` field.
+///
+/// Any keys besides the standard IPNS fields are kept in `extensions`.
 struct SignatureV2Data {
-  #[serde(with = "serde_bytes")]
-  Value: Vec<u8>,
-  #[serde(with = "serde_bytes")]
-  Validity: Vec<u8>,
-  ValidityType: i32,
-  Sequence: u64,
-  TTL: u64,
+  value: Vec<u8>,
+  validity: Vec<u8>,
+  validity_type: i32,
+  sequence: u64,
+  ttl: u64,
+  extensions: BTreeMap<String, CborValue>,
+}
+
+impl SignatureV2Data {
+  fn to_cbor_map(&self) -> BTreeMap<String, CborValue> {
+    let mut map = self.extensions.clone();
+    map.insert("Value".to_string(), CborValue::Bytes(self.value.clone()));
+    map.insert(
+      "Validity".to_string(),
+      CborValue::Bytes(self.validity.clone()),
+    );
+    map.insert(
+      "ValidityType".to_string(),
+      CborValue::Integer(self.validity_type.into()),
+    );
+    map.insert(
+      "Sequence".to_string(),
+      CborValue::Integer(self.sequence.into()),
+    );
+    map.insert("TTL".to_string(), CborValue::Integer(self.ttl.into()));
+    map
+  }
+
+  fn from_cbor_map(mut map: BTreeMap<String, CborValue>) -> Result<SignatureV2Data, CborError> {
+    let data = SignatureV2Data {
+      value: cbor_bytes(&map, "Value")?,
+      validity: cbor_bytes(&map, "Validity")?,
+      validity_type: cbor_integer(&map, "ValidityType")?,
+      sequence: cbor_integer(&map, "Sequence")?,
+      ttl: cbor_integer(&map, "TTL")?,
+      extensions: BTreeMap::new(),
+    };
+    map.retain(|key, _| !is_reserved_field(key));
+    Ok(SignatureV2Data {
+      extensions: map,
+      ..data
+    })
+  }
+}
+
+fn cbor_bytes(map: &BTreeMap<String, CborValue>, key: &str) -> Result<Vec<u8>, CborError> {
+  match map.get(key) {
+    Some(CborValue::Bytes(b)) => Ok(b.clone()),
+    _ => Err(report!(CborError).attach_printable(format!("missing or invalid {key} field"))),
+  }
+}
+
+fn cbor_integer<T: TryFrom<i128>>(
+  map: &BTreeMap<String, CborValue>,
+  key: &str,
+) -> Result<T, CborError> {
+  match map.get(key) {
+    Some(CborValue::Integer(i)) => T::try_from(*i)
+      .map_err(|_| report!(CborError).attach_printable(format!("{key} is out of range"))),
+    _ => Err(report!(CborError).attach_printable(format!("missing or invalid {key} field"))),
+  }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
   use crate::WritableName;
-  use chrono::Duration;
 
   #[test]
   fn to_ipns() {
     let name = WritableName::new();
     let value = "such value. much wow".to_string();
-    let validity = Utc::now().checked_add_signed(Duration::weeks(52)).unwrap();
+    let validity = Utc::now()
+      .checked_add_signed(chrono::Duration::weeks(52))
+      .unwrap();
     let rev = Revision::v0_with_validity(&name.to_name(), &value, validity);
     assert_eq!(rev.sequence(), 0);
     assert_eq!(rev.name(), &name.to_name());
-    assert_eq!(rev.value(), &value);
+    assert_eq!(rev.value(), value.as_bytes());
     assert_eq!(rev.validity(), &validity);
 
-    let entry = revision_to_ipns_entry(&rev, name.keypair()).unwrap();
+    let entry = revision_to_ipns_entry(&rev, name.keypair(), &SigningOptions::default()).unwrap();
     assert_eq!(rev.sequence(), entry.sequence);
-    assert_eq!(rev.value().as_bytes(), &entry.value);
+    assert_eq!(rev.value(), &entry.value);
     assert_eq!(rev.validity_string().as_bytes(), &entry.validity);
+    assert_eq!(entry.ttl, 60 * 60 * 1_000_000_000);
   }
 
   #[test]
@@ -220,11 +539,358 @@ mod tests {
     let value = "such value. much wow".to_string();
     let rev = Revision::v0(&name.to_name(), &value);
 
-    let entry = revision_to_ipns_entry(&rev, name.keypair()).unwrap();
+    let entry = revision_to_ipns_entry(&rev, name.keypair(), &SigningOptions::default()).unwrap();
 
-    validate_ipns_entry(&entry, &name.keypair().public()).unwrap();
+    validate_ipns_entry(&entry, &name.to_name(), &ValidationOptions::default()).unwrap();
 
     let rev2 = revision_from_ipns_entry(&entry, &name.to_name()).unwrap();
     assert_eq!(rev, rev2);
   }
+
+  #[test]
+  fn binary_value_round_trip() {
+    let name = WritableName::new();
+    let value = [0x01, 0x72, 0x00, 0x24, 0x08, 0x01, 0xff];
+    let rev = Revision::v0(&name.to_name(), value);
+
+    let entry = revision_to_ipns_entry(&rev, name.keypair(), &SigningOptions::default()).unwrap();
+    validate_ipns_entry(&entry, &name.to_name(), &ValidationOptions::default()).unwrap();
+
+    let rev2 = revision_from_ipns_entry(&entry, &name.to_name()).unwrap();
+    assert_eq!(rev2.value(), &value);
+  }
+
+  #[test]
+  fn rejects_expired_entry() {
+    let name = WritableName::new();
+    let validity = Utc::now()
+      .checked_add_signed(chrono::Duration::days(1))
+      .unwrap();
+    let rev = Revision::v0_with_validity(&name.to_name(), "a value", validity);
+    let entry = revision_to_ipns_entry(&rev, name.keypair(), &SigningOptions::default()).unwrap();
+    let n = name.to_name();
+
+    // still valid at the exact end of life timestamp
+    let at_eol = ValidationOptions::new().at_time(validity);
+    validate_ipns_entry(&entry, &n, &at_eol).unwrap();
+
+    let after_eol = ValidationOptions::new().at_time(validity + chrono::Duration::nanoseconds(1));
+    let err = validate_ipns_entry(&entry, &n, &after_eol).unwrap_err();
+    let expired: &RecordExpired = err.downcast_ref().expect("expected RecordExpired error");
+    assert_eq!(expired.expired_at, validity);
+
+    let allow_expired = after_eol.allow_expired(true);
+    validate_ipns_entry(&entry, &n, &allow_expired).unwrap();
+  }
+
+  #[test]
+  fn v2_only_entry() {
+    let name = WritableName::new();
+    let rev = Revision::v0(&name.to_name(), "a value");
+    let options = SigningOptions::new().v2_only(true);
+    let entry = revision_to_ipns_entry(&rev, name.keypair(), &options).unwrap();
+    assert!(entry.signature.is_empty());
+    assert!(!entry.signature_v2.is_empty());
+
+    let strict = ValidationOptions::new().require_v2(true);
+    validate_ipns_entry(&entry, &name.to_name(), &strict).unwrap();
+  }
+
+  #[test]
+  fn strict_validation_rejects_v1_only_entry() {
+    let name = WritableName::new();
+    let rev = Revision::v0(&name.to_name(), "a value");
+    let mut entry =
+      revision_to_ipns_entry(&rev, name.keypair(), &SigningOptions::default()).unwrap();
+    entry.signature_v2 = vec![];
+    entry.data = vec![];
+    let n = name.to_name();
+
+    validate_ipns_entry(&entry, &n, &ValidationOptions::default()).unwrap();
+
+    let strict = ValidationOptions::new().require_v2(true);
+    let err = validate_ipns_entry(&entry, &n, &strict).unwrap_err();
+    assert!(err.downcast_ref::<MissingIpnsV2Signature>().is_some());
+  }
+
+  #[test]
+  fn v2_signature_data_is_dag_cbor() {
+    // The expected bytes follow the DAG-CBOR rules used by go-ipns and js-ipns: keys sorted by
+    // length and then bytewise (TTL, Value, Sequence, Validity, ValidityType) and minimal integers.
+    let expected = "a56354544c1b0000034630b8a0006556616c75654568656c6c6f6853657175656e6365016856616c6964697479581e323033332d30352d31385430333a33333a32302e3030303030303030305a6c56616c69646974795479706500";
+    let data = v2_signature_data(
+      b"hello",
+      "2033-05-18T03:33:20.000000000Z",
+      1,
+      3_600_000_000_000,
+      &BTreeMap::new(),
+    )
+    .unwrap();
+    assert_eq!(hex(&data), expected);
+  }
+
+  #[test]
+  fn validates_entry_with_unsorted_v2_data() {
+    // older versions of this crate wrote the v2 data in struct field order
+    #[allow(non_snake_case)]
+    #[derive(serde::Serialize)]
+    struct LegacySignatureV2Data {
+      #[serde(with = "serde_bytes")]
+      Value: Vec<u8>,
+      #[serde(with = "serde_bytes")]
+      Validity: Vec<u8>,
+      ValidityType: i32,
+      Sequence: u64,
+      TTL: u64,
+    }
+
+    let name = WritableName::new();
+    let rev = Revision::v0(&name.to_name(), "a value");
+    let mut entry =
+      revision_to_ipns_entry(&rev, name.keypair(), &SigningOptions::default()).unwrap();
+    entry.data = serde_cbor::to_vec(&LegacySignatureV2Data {
+      Value: entry.value.clone(),
+      Validity: entry.validity.clone(),
+      ValidityType: 0,
+      Sequence: entry.sequence,
+      TTL: entry.ttl,
+    })
+    .unwrap();
+    assert!(!dag_cbor::is_canonical(&entry.data));
+    entry.signature_v2 = create_v2_signature(name.keypair(), &entry.data).unwrap();
+
+    validate_ipns_entry(&entry, &name.to_name(), &ValidationOptions::default()).unwrap();
+  }
+
+  #[test]
+  fn extension_fields_are_signed_and_preserved() {
+    let name = WritableName::new();
+    let mut rev = Revision::v0(&name.to_name(), "a value");
+    rev.set_extension("note", &"hello").unwrap();
+
+    let entry = revision_to_ipns_entry(&rev, name.keypair(), &SigningOptions::default()).unwrap();
+    let n = name.to_name();
+    validate_ipns_entry(&entry, &n, &ValidationOptions::default()).unwrap();
+
+    let resolved = revision_from_ipns_entry(&entry, &name.to_name()).unwrap();
+    assert_eq!(resolved, rev);
+
+    // republishing the resolved revision produces the same signed data
+    let republished =
+      revision_to_ipns_entry(&resolved, name.keypair(), &SigningOptions::default()).unwrap();
+    assert_eq!(republished.data, entry.data);
+
+    // tampering with the extension fields invalidates the signature
+    let mut tampered = rev;
+    tampered.set_extension("note", &"goodbye").unwrap();
+    let mut tampered_entry = entry.clone();
+    tampered_entry.data = v2_signature_data(
+      tampered.value(),
+      &tampered.validity_string(),
+      tampered.sequence(),
+      entry.ttl,
+      tampered.extensions(),
+    )
+    .unwrap();
+    assert!(validate_ipns_entry(&tampered_entry, &n, &ValidationOptions::default()).is_err());
+  }
+
+  #[test]
+  fn extension_fields_need_a_v2_signature() {
+    let name = WritableName::new();
+    let n = name.to_name();
+    let rev = Revision::v0(&n, "a value");
+    let entry = revision_to_ipns_entry(&rev, name.keypair(), &SigningOptions::default()).unwrap();
+
+    // the v1 signature still verifies, but it doesn't cover the injected fields
+    let mut forged_rev = Revision::v0_with_validity(&n, "a value", *rev.validity());
+    forged_rev.set_extension("note", &"forged").unwrap();
+    let mut forged = entry.clone();
+    forged.signature_v2 = vec![];
+    forged.data = v2_signature_data(
+      forged_rev.value(),
+      &forged_rev.validity_string(),
+      forged_rev.sequence(),
+      entry.ttl,
+      forged_rev.extensions(),
+    )
+    .unwrap();
+    validate_ipns_entry(&forged, &n, &ValidationOptions::default()).unwrap();
+    assert_eq!(signed_v2_data(&forged, &n).unwrap(), None);
+    assert!(revision_from_ipns_entry(&forged, &n)
+      .unwrap()
+      .extensions()
+      .is_empty());
+
+    // a v2 signature from another key doesn't count either
+    forged.signature_v2 = create_v2_signature(WritableName::new().keypair(), &forged.data).unwrap();
+    assert_eq!(signed_v2_data(&forged, &n).unwrap(), None);
+  }
+
+  #[test]
+  fn rejects_oversized_entries() {
+    let name = WritableName::new();
+    let rev = Revision::v0(&name.to_name(), vec![b'a'; MAX_RECORD_SIZE]);
+    let err = revision_to_ipns_entry(&rev, name.keypair(), &SigningOptions::default()).unwrap_err();
+    let too_large: &RecordTooLarge = err.downcast_ref().expect("expected RecordTooLarge error");
+    assert!(too_large.size > MAX_RECORD_SIZE);
+
+    let err = deserialize_ipns_entry(&vec![0; MAX_RECORD_SIZE + 1]).unwrap_err();
+    assert!(err.downcast_ref::<ReceivedRecordTooLarge>().is_some());
+  }
+
+  #[test]
+  fn embeds_public_key_only_when_needed() {
+    let secp = WritableName::new_secp256k1();
+    let rev = Revision::v0(&secp.to_name(), "a value");
+    let entry = revision_to_ipns_entry(&rev, secp.keypair(), &SigningOptions::default()).unwrap();
+    assert!(entry.pub_key.is_empty());
+    validate_ipns_entry(&entry, &secp.to_name(), &ValidationOptions::default()).unwrap();
+
+    let ecdsa = WritableName::new_ecdsa();
+    let rev = Revision::v0(&ecdsa.to_name(), "a value");
+    let entry = revision_to_ipns_entry(&rev, ecdsa.keypair(), &SigningOptions::default()).unwrap();
+    assert_eq!(
+      entry.pub_key,
+      ecdsa.keypair().public().to_protobuf_encoding()
+    );
+
+    // a parsed name only has the hash of the key, so the key comes from the entry
+    let parsed = Name::parse(ecdsa.to_string()).unwrap();
+    validate_ipns_entry(&entry, &parsed, &ValidationOptions::default()).unwrap();
+
+    let mut missing_key = entry.clone();
+    missing_key.pub_key = vec![];
+    let err =
+      validate_ipns_entry(&missing_key, &parsed, &ValidationOptions::default()).unwrap_err();
+    assert!(err.downcast_ref::<MissingPublicKey>().is_some());
+  }
+
+  #[tokio::test]
+  async fn signing_with_fixed_clock_is_reproducible() {
+    use crate::{Clock, Succession, Value};
+
+    let clock = crate::FixedClock::new(parse_validity(b"2022-09-23T00:00:00Z").unwrap());
+    let validity = clock.now() + chrono::Duration::weeks(52);
+    let ttl = Duration::from_secs(300);
+    let value =
+      Value::parse("/ipfs/bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi").unwrap();
+    let successor = WritableName::new().to_name();
+
+    for name in [
+      WritableName::new(),
+      WritableName::new_secp256k1(),
+      WritableName::new_ecdsa(),
+    ] {
+      let n = name.to_name();
+      let succession = Succession::sign(&name, &successor).await.unwrap();
+      // every constructor that picks the default validity, called with the fixed clock
+      let revisions = || {
+        let rev = Revision::v0_with_clock(&n, "a value", &clock);
+        vec![
+          Revision::v0_with_ttl_and_clock(&n, "a value", ttl, &clock),
+          Revision::v0_value_with_clock(&n, &value, &clock),
+          Revision::v0_succession_with_clock(&n, &succession, &clock),
+          rev.increment_with_clock("another value", &clock),
+          rev.increment_with_ttl_and_clock("another value", ttl, &clock),
+          rev.increment_value_with_clock(&value, &clock),
+          rev.increment_succession_with_clock(&succession, &clock),
+          rev,
+        ]
+      };
+      let sign = |rev: &Revision| {
+        let entry =
+          revision_to_ipns_entry(rev, name.keypair(), &SigningOptions::default()).unwrap();
+        serialize_ipns_entry(&entry).unwrap()
+      };
+
+      for (a, b) in revisions().iter().zip(&revisions()) {
+        assert_eq!(a.validity(), &validity);
+        assert_eq!(sign(a), sign(b));
+      }
+    }
+  }
+
+  #[test]
+  fn rejects_public_key_for_another_name() {
+    let name = WritableName::new_ecdsa();
+    let other = WritableName::new_ecdsa();
+    let rev = Revision::v0(&other.to_name(), "a value");
+    let entry = revision_to_ipns_entry(&rev, other.keypair(), &SigningOptions::default()).unwrap();
+
+    let err =
+      validate_ipns_entry(&entry, &name.to_name(), &ValidationOptions::default()).unwrap_err();
+    assert!(err.downcast_ref::<PublicKeyMismatch>().is_some());
+  }
+
+  /// Checks the records in `testdata/`, which were built independently of this crate. See the README there.
+  #[test]
+  fn reference_records() {
+    // the key the fixtures are signed with, see testdata/generate.py
+    let seed: [u8; 32] = std::array::from_fn(|i| i as u8 + 1);
+    let key = WritableName::from_seed(seed);
+
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src/ipns/testdata");
+    let mut checked = 0;
+    for path in std::fs::read_dir(dir).unwrap() {
+      let path = path.unwrap().path();
+      if path.extension().and_then(|ext| ext.to_str()) != Some("ipns-record") {
+        continue;
+      }
+      let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+      let (name, description) = file_name.split_once('_').expect(&file_name);
+      let name = Name::parse(name).unwrap();
+      checked += 1;
+
+      let bytes = std::fs::read(&path).unwrap();
+      let entry = deserialize_ipns_entry(&bytes).unwrap();
+      let options = ValidationOptions::new().allow_expired(true);
+      let result = validate_ipns_entry(&entry, &name, &options);
+      if description.contains("invalid") {
+        assert!(result.is_err(), "{file_name} should fail validation");
+        continue;
+      }
+      result.unwrap();
+
+      // encoding the same fields has to reproduce the signed data exactly
+      let data = dag_cbor::decode_map(&entry.data)
+        .and_then(SignatureV2Data::from_cbor_map)
+        .unwrap();
+      let validity = parse_validity(&data.validity).unwrap();
+      let rev = Revision::new(
+        &name,
+        &data.value,
+        validity,
+        data.sequence,
+        Duration::from_nanos(data.ttl),
+      )
+      .with_extensions(data.extensions);
+      assert_eq!(rev.validity_string().as_bytes(), &data.validity[..]);
+      let unsigned = UnsignedEntry::new(&rev).unwrap();
+      assert_eq!(hex(&unsigned.data), hex(&entry.data), "{file_name}");
+
+      // ed25519 signatures are deterministic, so signing them again with the same key has to reproduce
+      // the signatures and the whole record as well
+      if name == key.to_name() {
+        let options = SigningOptions::new().v2_only(entry.signature.is_empty());
+        let signed = revision_to_ipns_entry(&rev, key.keypair(), &options).unwrap();
+        assert_eq!(
+          hex(&signed.signature_v2),
+          hex(&entry.signature_v2),
+          "{file_name}"
+        );
+        assert_eq!(hex(&signed.signature), hex(&entry.signature), "{file_name}");
+        assert_eq!(
+          hex(&serialize_ipns_entry(&signed).unwrap()),
+          hex(&bytes),
+          "{file_name}"
+        );
+      }
+    }
+    assert_ne!(checked, 0, "no .ipns-record fixtures found in testdata/");
+  }
+
+  fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+  }
 }
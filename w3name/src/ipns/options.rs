@@ -0,0 +1,133 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+
+use crate::{Clock, FixedClock, SystemClock};
+
+/// Options that control how IPNS records are validated.
+///
+/// By default, records whose validity (end of life) timestamp is in the past are rejected with a
+/// [RecordExpired](crate::error::RecordExpired) error. Tools that need to inspect old records can
+/// opt out with [allow_expired](Self::allow_expired).
+///
+/// Records that only carry a legacy (v1) signature are accepted unless [require_v2](Self::require_v2) is set.
+///
+/// Expiry is checked against the [SystemClock] by default. Use [clock](Self::clock) to check against another [Clock].
+///
+/// ## Example
+///
+/// ```rust
+/// use w3name::ValidationOptions;
+/// use chrono::{TimeZone, Utc};
+///
+/// // accept expired records, and check validity as of a fixed point in time
+/// let options = ValidationOptions::new()
+///   .allow_expired(true)
+///   .at_time(Utc.ymd(2022, 9, 23).and_hms(0, 0, 0));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ValidationOptions {
+  allow_expired: bool,
+  require_v2: bool,
+  clock: Arc<dyn Clock>,
+}
+
+impl Default for ValidationOptions {
+  fn default() -> Self {
+    ValidationOptions {
+      allow_expired: false,
+      require_v2: false,
+      clock: Arc::new(SystemClock),
+    }
+  }
+}
+
+impl ValidationOptions {
+  /// Creates the default `ValidationOptions`, which reject expired records.
+  pub fn new() -> ValidationOptions {
+    ValidationOptions::default()
+  }
+
+  /// Sets whether records whose validity period has ended should be accepted.
+  pub fn allow_expired(mut self, allow: bool) -> ValidationOptions {
+    self.allow_expired = allow;
+    self
+  }
+
+  /// Sets whether records must carry a valid v2 signature.
+  ///
+  /// When set, records without a v2 signature are rejected with a
+  /// [MissingIpnsV2Signature](crate::error::MissingIpnsV2Signature) error instead of falling back to the v1 signature.
+  pub fn require_v2(mut self, require: bool) -> ValidationOptions {
+    self.require_v2 = require;
+    self
+  }
+
+  /// Sets the [Clock] that record expiry is checked against.
+  pub fn clock<C: Clock + 'static>(mut self, clock: C) -> ValidationOptions {
+    self.clock = Arc::new(clock);
+    self
+  }
+
+  /// Checks record expiry against the given time instead of the current system time.
+  ///
+  /// This is a shorthand for setting a [FixedClock] with [clock](Self::clock).
+  pub fn at_time(self, now: DateTime<Utc>) -> ValidationOptions {
+    self.clock(FixedClock::new(now))
+  }
+
+  pub(crate) fn allows_expired(&self) -> bool {
+    self.allow_expired
+  }
+
+  pub(crate) fn requires_v2(&self) -> bool {
+    self.require_v2
+  }
+
+  pub(crate) fn now(&self) -> DateTime<Utc> {
+    self.clock.now()
+  }
+}
+
+/// Options that control how IPNS records are signed.
+///
+/// By default, records carry both a v2 signature and a legacy (v1) signature, so that older IPNS
+/// implementations can still verify them. Since v1 signatures are deprecated, you can create
+/// v2-only records with [v2_only](Self::v2_only).
+///
+/// ## Example
+///
+/// ```rust
+/// # fn main() -> error_stack::Result<(), w3name::error::IpnsError> {
+/// use w3name::{IpnsRecord, Revision, SigningOptions, WritableName};
+///
+/// let name = WritableName::new();
+/// let rev = Revision::v0(&name.to_name(), "an initial value");
+///
+/// let options = SigningOptions::new().v2_only(true);
+/// let record = IpnsRecord::new_with_options(&name, &rev, &options)?;
+/// assert!(record.signature_v1().is_empty());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SigningOptions {
+  v2_only: bool,
+}
+
+impl SigningOptions {
+  /// Creates the default `SigningOptions`, which add both v1 and v2 signatures.
+  pub fn new() -> SigningOptions {
+    SigningOptions::default()
+  }
+
+  /// Sets whether to leave out the legacy (v1) signature.
+  pub fn v2_only(mut self, v2_only: bool) -> SigningOptions {
+    self.v2_only = v2_only;
+    self
+  }
+
+  pub(crate) fn is_v2_only(&self) -> bool {
+    self.v2_only
+  }
+}
@@ -0,0 +1,335 @@
+use crate::{
+  error::{InvalidExtensionField, IpnsError},
+  ipns_pb::IpnsEntry,
+  revision::extension_from_map,
+  Name, Revision, Signer, WritableName,
+};
+use error_stack::{Result, ResultExt};
+use serde::de::DeserializeOwned;
+use std::cmp::Ordering;
+
+use super::{
+  compare_ipns_entries, dag_cbor, deserialize_ipns_entry, is_reserved_field,
+  report::verification_report, revision_from_ipns_entry, revision_to_ipns_entry,
+  serialize_ipns_entry, sign_ipns_entry, signed_v2_data, validate_ipns_entry, SigningOptions,
+  ValidationOptions, VerificationReport,
+};
+
+/// An `IpnsRecord` is a signed IPNS entry, as published to and resolved from the w3name service.
+///
+/// Where a [Revision] only holds the unsigned contents of a name record, an `IpnsRecord` also keeps
+/// the signatures, TTL and the rest of the protobuf fields, so it can be archived, forwarded to another
+/// IPNS node or re-verified later without going through the [W3NameClient](crate::W3NameClient).
+///
+/// To create a record, sign a [Revision] with [IpnsRecord::new], or with [IpnsRecord::sign] if the private key
+/// is held by a [Signer]. Records can be converted to and from
+/// the standard protobuf encoding with [encode](IpnsRecord::encode) and [decode](IpnsRecord::decode).
+///
+/// ## Example
+///
+/// ```rust
+/// # fn main() -> error_stack::Result<(), w3name::error::IpnsError> {
+/// use w3name::{IpnsRecord, Revision, WritableName};
+///
+/// let name = WritableName::new();
+/// let rev = Revision::v0(&name.to_name(), "an initial value");
+///
+/// let record = IpnsRecord::new(&name, &rev)?;
+/// let bytes = record.encode()?;
+///
+/// let decoded = IpnsRecord::decode(&bytes)?;
+/// decoded.verify(&name.to_name())?;
+///
+/// assert_eq!(decoded.to_revision(&name.to_name())?, rev);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct IpnsRecord(IpnsEntry);
+
+impl IpnsRecord {
+  /// Creates a new `IpnsRecord` by signing the given [Revision] with the private key of a [WritableName].
+  ///
+  /// Note that this does not check that the `revision` belongs to the `name`. Records larger than
+  /// [MAX_RECORD_SIZE](crate::MAX_RECORD_SIZE) are rejected with a [RecordTooLarge](crate::error::RecordTooLarge) error.
+  pub fn new(name: &WritableName, revision: &Revision) -> Result<IpnsRecord, IpnsError> {
+    Self::new_with_options(name, revision, &SigningOptions::default())
+  }
+
+  /// Creates a new `IpnsRecord` like [new](Self::new), using the given [SigningOptions].
+  pub fn new_with_options(
+    name: &WritableName,
+    revision: &Revision,
+    options: &SigningOptions,
+  ) -> Result<IpnsRecord, IpnsError> {
+    let entry = revision_to_ipns_entry(revision, name.keypair(), options)?;
+    Ok(IpnsRecord(entry))
+  }
+
+  /// Creates a new `IpnsRecord` by signing the given [Revision] with a [Signer].
+  ///
+  /// Unlike [new](Self::new), this works with keys that are kept outside of this process. The signatures
+  /// are checked against the signer's public key, and a [SigningError](crate::error::SigningError) is returned
+  /// if they don't match.
+  pub async fn sign<S: Signer + ?Sized>(
+    signer: &S,
+    revision: &Revision,
+  ) -> Result<IpnsRecord, IpnsError> {
+    Self::sign_with_options(signer, revision, &SigningOptions::default()).await
+  }
+
+  /// Creates a new `IpnsRecord` like [sign](Self::sign), using the given [SigningOptions].
+  pub async fn sign_with_options<S: Signer + ?Sized>(
+    signer: &S,
+    revision: &Revision,
+    options: &SigningOptions,
+  ) -> Result<IpnsRecord, IpnsError> {
+    let entry = sign_ipns_entry(revision, signer, options).await?;
+    Ok(IpnsRecord(entry))
+  }
+
+  /// Decodes an `IpnsRecord` from the binary protobuf encoding used by IPNS.
+  ///
+  /// Inputs larger than [MAX_RECORD_SIZE](crate::MAX_RECORD_SIZE) are rejected with a
+  /// [ReceivedRecordTooLarge](crate::error::ReceivedRecordTooLarge) error.
+  /// The record is not verified; use [verify](Self::verify) to check its signatures.
+  pub fn decode(bytes: &[u8]) -> Result<IpnsRecord, IpnsError> {
+    let entry = deserialize_ipns_entry(bytes)?;
+    Ok(IpnsRecord(entry))
+  }
+
+  /// Encodes this `IpnsRecord` to the binary protobuf encoding used by IPNS.
+  pub fn encode(&self) -> Result<Vec<u8>, IpnsError> {
+    serialize_ipns_entry(&self.0)
+  }
+
+  /// Verifies the signatures of this `IpnsRecord` against the public key of the given [Name],
+  /// and checks that the record has not expired.
+  pub fn verify(&self, name: &Name) -> Result<(), IpnsError> {
+    self.verify_with_options(name, &ValidationOptions::default())
+  }
+
+  /// Verifies this `IpnsRecord` against the given [Name], using the given [ValidationOptions].
+  pub fn verify_with_options(
+    &self,
+    name: &Name,
+    options: &ValidationOptions,
+  ) -> Result<(), IpnsError> {
+    validate_ipns_entry(&self.0, name, options)
+  }
+
+  /// Runs the same checks as [verify_with_options](Self::verify_with_options), but returns a
+  /// [VerificationReport] with the outcome of every check instead of stopping at the first failure.
+  ///
+  /// This is meant for diagnosing records that fail verification.
+  pub fn verification_report(
+    &self,
+    name: &Name,
+    options: &ValidationOptions,
+  ) -> VerificationReport {
+    verification_report(&self.0, name, options)
+  }
+
+  /// Converts this `IpnsRecord` back into an unsigned [Revision] for the given [Name].
+  ///
+  /// Note that this does not verify the record. If the record came from an untrusted source,
+  /// call [verify](Self::verify) first.
+  pub fn to_revision(&self, name: &Name) -> Result<Revision, IpnsError> {
+    revision_from_ipns_entry(&self.0, name)
+  }
+
+  /// Compares two records for the same name by how authoritative they are, following the IPNS ordering rules.
+  ///
+  /// The record with the higher sequence number wins. If the sequence numbers are equal, the record with the later
+  /// validity wins, and a record whose validity can't be parsed loses to any record with a valid one. Any remaining
+  /// ties are broken deterministically by comparing the encoded records, so caches, mirrors and clients all pick
+  /// the same record. `Ordering::Greater` means that `self` should be preferred.
+  ///
+  /// This only compares the records; call [verify](Self::verify) on records from untrusted sources first.
+  pub fn compare(&self, other: &IpnsRecord) -> Ordering {
+    compare_ipns_entries(&self.0, &other.0)
+  }
+
+  /// Returns the most authoritative of the given records according to [compare](Self::compare),
+  /// or `None` if there are none.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// # fn main() -> error_stack::Result<(), w3name::error::IpnsError> {
+  /// use w3name::{IpnsRecord, Revision, WritableName};
+  ///
+  /// let name = WritableName::new();
+  /// let rev0 = Revision::v0(&name.to_name(), "first");
+  /// let rev1 = rev0.increment("second");
+  ///
+  /// let archived = IpnsRecord::new(&name, &rev0)?;
+  /// let latest = IpnsRecord::new(&name, &rev1)?;
+  ///
+  /// assert_eq!(IpnsRecord::best([&archived, &latest]), Some(&latest));
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn best<'a, I: IntoIterator<Item = &'a IpnsRecord>>(records: I) -> Option<&'a IpnsRecord> {
+    records.into_iter().max_by(|a, b| a.compare(b))
+  }
+
+  /// Returns the raw value bytes stored in this record.
+  pub fn value(&self) -> &[u8] {
+    &self.0.value
+  }
+
+  /// Returns the raw validity (end of life) bytes, which hold an RFC-3339 timestamp.
+  pub fn validity(&self) -> &[u8] {
+    &self.0.validity
+  }
+
+  /// Returns this record's sequence number.
+  pub fn sequence(&self) -> u64 {
+    self.0.sequence
+  }
+
+  /// Returns this record's TTL in nanoseconds.
+  pub fn ttl(&self) -> u64 {
+    self.0.ttl
+  }
+
+  /// Returns the legacy (v1) signature, which may be empty.
+  pub fn signature_v1(&self) -> &[u8] {
+    &self.0.signature
+  }
+
+  /// Returns the v2 signature, which may be empty for records created by older implementations.
+  pub fn signature_v2(&self) -> &[u8] {
+    &self.0.signature_v2
+  }
+
+  /// Returns the CBOR-encoded data covered by the v2 signature.
+  pub fn data(&self) -> &[u8] {
+    &self.0.data
+  }
+
+  /// Returns the extension field with the given key from the signed v2 [data](Self::data), deserialized as `T`.
+  ///
+  /// Returns `None` if the record has no such field, or if it has no v2 signature from the key of `name`, since only
+  /// the v2 signature covers the extension fields. See [Revision::set_extension] for how to add extension fields.
+  pub fn extension<T: DeserializeOwned>(
+    &self,
+    name: &Name,
+    key: &str,
+  ) -> Result<Option<T>, InvalidExtensionField> {
+    if is_reserved_field(key) {
+      return Ok(None);
+    }
+    match signed_v2_data(&self.0, name).change_context(InvalidExtensionField)? {
+      Some(map) => extension_from_map(&map, key),
+      None => Ok(None),
+    }
+  }
+
+  /// Returns true if the v2 [data](Self::data) is encoded as canonical DAG-CBOR.
+  ///
+  /// Records created by this crate always are, but records signed by older implementations may
+  /// list the map keys in a different order. Such records still verify, since the signature covers the bytes as-is.
+  pub fn has_canonical_data(&self) -> bool {
+    dag_cbor::is_canonical(&self.0.data)
+  }
+
+  /// Returns the public key embedded in this record, which is empty if the key is part of the name itself.
+  pub fn public_key_bytes(&self) -> &[u8] {
+    &self.0.pub_key
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn encode_decode_round_trip() {
+    let name = WritableName::new();
+    let rev = Revision::v0(&name.to_name(), "such value. much wow");
+    let record = IpnsRecord::new(&name, &rev).unwrap();
+
+    let bytes = record.encode().unwrap();
+    let decoded = IpnsRecord::decode(&bytes).unwrap();
+    assert_eq!(record, decoded);
+
+    decoded.verify(&name.to_name()).unwrap();
+    assert_eq!(decoded.to_revision(&name.to_name()).unwrap(), rev);
+    assert_eq!(decoded.sequence(), 0);
+    assert_eq!(decoded.value(), b"such value. much wow");
+    assert!(!decoded.signature_v2().is_empty());
+    assert!(decoded.has_canonical_data());
+  }
+
+  #[test]
+  fn read_extension_fields() {
+    let name = WritableName::new();
+    let mut rev = Revision::v0(&name.to_name(), "a value");
+    rev.set_extension("count", &3u8).unwrap();
+    let record = IpnsRecord::new(&name, &rev).unwrap();
+    let n = name.to_name();
+
+    assert_eq!(record.extension::<u8>(&n, "count").unwrap(), Some(3));
+    assert_eq!(record.extension::<u8>(&n, "missing").unwrap(), None);
+    assert_eq!(record.extension::<u64>(&n, "Sequence").unwrap(), None);
+
+    // without a v2 signature, nothing vouches for the data
+    let mut unsigned = record.clone();
+    unsigned.0.signature_v2 = vec![];
+    assert_eq!(unsigned.extension::<u8>(&n, "count").unwrap(), None);
+    assert_eq!(
+      record
+        .extension::<u8>(&WritableName::new().to_name(), "count")
+        .unwrap(),
+      None
+    );
+  }
+
+  #[test]
+  fn verify_rejects_wrong_name() {
+    let name = WritableName::new();
+    let other = WritableName::new();
+    let rev = Revision::v0(&name.to_name(), "a value");
+    let record = IpnsRecord::new(&name, &rev).unwrap();
+
+    assert!(record.verify(&other.to_name()).is_err());
+  }
+
+  #[test]
+  fn compare_records() {
+    let name = WritableName::new();
+    let rev0 = Revision::v0(&name.to_name(), "a value");
+    let rev1 = rev0.increment("another value");
+    let old = IpnsRecord::new(&name, &rev0).unwrap();
+    let new = IpnsRecord::new(&name, &rev1).unwrap();
+    assert_eq!(new.compare(&old), Ordering::Greater);
+    assert_eq!(old.compare(&new), Ordering::Less);
+
+    // with equal sequence numbers, the later validity wins, even over a higher value
+    let validity = *rev0.validity();
+    let early = Revision::v0_with_validity(&name.to_name(), "b", validity);
+    let late =
+      Revision::v0_with_validity(&name.to_name(), "a", validity + chrono::Duration::days(1));
+    let early = IpnsRecord::new(&name, &early).unwrap();
+    let late = IpnsRecord::new(&name, &late).unwrap();
+    assert_eq!(IpnsRecord::best([&early, &late]), Some(&late));
+    assert_eq!(IpnsRecord::best([&late, &early]), Some(&late));
+
+    // full ties are broken the same way regardless of order
+    let a = IpnsRecord::new(
+      &name,
+      &Revision::v0_with_validity(&name.to_name(), "a", validity),
+    )
+    .unwrap();
+    let b = IpnsRecord::new(
+      &name,
+      &Revision::v0_with_validity(&name.to_name(), "b", validity),
+    )
+    .unwrap();
+    assert_eq!(IpnsRecord::best([&a, &b]), IpnsRecord::best([&b, &a]));
+    assert_eq!(a.compare(&a), Ordering::Equal);
+    assert_eq!(IpnsRecord::best([]), None);
+  }
+}
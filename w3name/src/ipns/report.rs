@@ -0,0 +1,504 @@
+use std::fmt::Display;
+
+use error_stack::{AttachmentKind, FrameKind};
+use libp2p_core::identity::PublicKey;
+use serde_cbor::Value as CborValue;
+
+use crate::{ipns_pb::IpnsEntry, Name};
+
+use super::{
+  dag_cbor, entry_public_key, parse_validity, v2_data_mismatches, validate_v1_signature,
+  validate_v2_signature, SignatureV2Data, ValidationOptions, RESERVED_V2_FIELDS,
+};
+
+/// A detailed account of the checks performed when verifying an [IpnsRecord](crate::IpnsRecord).
+///
+/// [IpnsRecord::verify](crate::IpnsRecord::verify) stops at the first failure and only returns an error,
+/// which makes it hard to tell what is wrong with a record. A `VerificationReport` runs the same checks,
+/// records the outcome of each one with the exact reason for any failure, and lists the protobuf fields
+/// of the record next to the fields in its signed v2 `data` map.
+///
+/// A record passes [verify_with_options](crate::IpnsRecord::verify_with_options) if and only if
+/// [is_valid](Self::is_valid) returns true for the report created with the same [ValidationOptions].
+///
+/// The [Display] implementation renders the whole report in a human-readable form.
+///
+/// ## Example
+///
+/// ```rust
+/// # fn main() -> error_stack::Result<(), w3name::error::IpnsError> {
+/// use w3name::{CheckOutcome, IpnsRecord, Revision, SignatureVersion, ValidationOptions, WritableName};
+///
+/// let name = WritableName::new();
+/// let record = IpnsRecord::new(&name, &Revision::v0(&name.to_name(), "a value"))?;
+///
+/// let report = record.verification_report(&name.to_name(), &ValidationOptions::default());
+/// assert!(report.is_valid());
+/// assert_eq!(report.signature_version(), Some(SignatureVersion::V2));
+/// assert!(report
+///   .checks()
+///   .iter()
+///   .all(|check| !matches!(check.outcome, CheckOutcome::Failed(_))));
+///
+/// println!("{report}");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerificationReport {
+  checks: Vec<VerificationCheck>,
+  signature_version: Option<SignatureVersion>,
+  fields: Vec<RecordField>,
+}
+
+/// A single check in a [VerificationReport].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerificationCheck {
+  pub kind: CheckKind,
+  pub outcome: CheckOutcome,
+}
+
+/// The checks performed when verifying a record, in the order they are performed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheckKind {
+  /// The public key used to verify the signatures is known and belongs to the name.
+  PublicKey,
+
+  /// The v2 signature is present and valid for the `data` field.
+  SignatureV2,
+
+  /// The fields in the signed `data` map match the protobuf fields of the record.
+  SignatureV2Data,
+
+  /// The legacy v1 signature is valid. This is only checked for records without a v2 signature.
+  SignatureV1,
+
+  /// The record's validity (end of life) timestamp has not passed.
+  NotExpired,
+}
+
+/// The outcome of a [VerificationCheck].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CheckOutcome {
+  Passed,
+
+  /// The check failed for the given reason.
+  Failed(String),
+
+  /// The check was not performed, for the given reason.
+  Skipped(String),
+}
+
+/// The signature version that a record was verified with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignatureVersion {
+  V1,
+  V2,
+}
+
+/// A field of a record, as stored in the protobuf encoding and in the signed v2 `data` map.
+///
+/// Values are rendered for display: byte strings that are valid UTF-8 are shown as quoted strings,
+/// and other byte strings in hex. Extension fields only exist in the `data` map, so their `protobuf` value is `None`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordField {
+  pub name: String,
+  pub protobuf: Option<String>,
+  pub data: Option<String>,
+}
+
+impl RecordField {
+  /// Returns true if the field has the same value in the protobuf encoding and the `data` map.
+  pub fn matches(&self) -> bool {
+    self.protobuf == self.data
+  }
+}
+
+impl VerificationReport {
+  /// Returns true if none of the checks failed.
+  pub fn is_valid(&self) -> bool {
+    self
+      .checks
+      .iter()
+      .all(|check| !matches!(check.outcome, CheckOutcome::Failed(_)))
+  }
+
+  /// Returns all checks, in the order they were performed.
+  pub fn checks(&self) -> &[VerificationCheck] {
+    &self.checks
+  }
+
+  /// Returns the outcome of the given check.
+  pub fn outcome(&self, kind: CheckKind) -> &CheckOutcome {
+    // every report contains each kind of check exactly once
+    &self
+      .checks
+      .iter()
+      .find(|check| check.kind == kind)
+      .expect("report is missing a check")
+      .outcome
+  }
+
+  /// Returns the signature version that was checked, or `None` if no signature could be checked.
+  pub fn signature_version(&self) -> Option<SignatureVersion> {
+    self.signature_version
+  }
+
+  /// Returns the standard IPNS fields followed by any extension fields, with their protobuf and `data` values.
+  pub fn fields(&self) -> &[RecordField] {
+    &self.fields
+  }
+}
+
+impl Display for CheckKind {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let s = match self {
+      CheckKind::PublicKey => "public key",
+      CheckKind::SignatureV2 => "v2 signature",
+      CheckKind::SignatureV2Data => "v2 signature data",
+      CheckKind::SignatureV1 => "v1 signature",
+      CheckKind::NotExpired => "not expired",
+    };
+    write!(f, "{s}")
+  }
+}
+
+impl Display for CheckOutcome {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      CheckOutcome::Passed => write!(f, "passed"),
+      CheckOutcome::Failed(reason) => write!(f, "FAILED: {reason}"),
+      CheckOutcome::Skipped(reason) => write!(f, "skipped: {reason}"),
+    }
+  }
+}
+
+impl Display for VerificationReport {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let status = if self.is_valid() { "valid" } else { "INVALID" };
+    writeln!(f, "record is {status}")?;
+
+    writeln!(f, "checks:")?;
+    for check in &self.checks {
+      writeln!(f, "  {}: {}", check.kind, check.outcome)?;
+    }
+
+    writeln!(f, "fields (protobuf | data):")?;
+    for field in &self.fields {
+      let protobuf = field.protobuf.as_deref().unwrap_or("-");
+      let data = field.data.as_deref().unwrap_or("-");
+      let marker = if field.protobuf.is_some() && !field.matches() {
+        "  <- mismatch"
+      } else {
+        ""
+      };
+      writeln!(f, "  {}: {protobuf} | {data}{marker}", field.name)?;
+    }
+    Ok(())
+  }
+}
+
+/// Runs the checks performed by [validate_ipns_entry](super::validate_ipns_entry) and records the outcome of each one.
+pub fn verification_report(
+  entry: &IpnsEntry,
+  name: &Name,
+  options: &ValidationOptions,
+) -> VerificationReport {
+  let mut checks = Vec::new();
+  let mut signature_version = None;
+
+  let public_key = check_public_key(entry, name);
+  checks.push(VerificationCheck {
+    kind: CheckKind::PublicKey,
+    outcome: match &public_key {
+      Ok(_) => CheckOutcome::Passed,
+      Err(reason) => CheckOutcome::Failed(reason.clone()),
+    },
+  });
+
+  let has_v2 = !entry.signature_v2.is_empty() && !entry.data.is_empty();
+  let decoded = dag_cbor::decode_map(&entry.data);
+
+  match &public_key {
+    Err(_) => {
+      let reason = "no public key to verify with".to_string();
+      checks.push(skipped(CheckKind::SignatureV2, &reason));
+      checks.push(skipped(CheckKind::SignatureV2Data, &reason));
+      checks.push(skipped(CheckKind::SignatureV1, &reason));
+    }
+    Ok(public_key) if has_v2 => {
+      signature_version = Some(SignatureVersion::V2);
+      checks.push(VerificationCheck {
+        kind: CheckKind::SignatureV2,
+        outcome: match validate_v2_signature(public_key, &entry.signature_v2, &entry.data) {
+          Ok(()) => CheckOutcome::Passed,
+          Err(_) => CheckOutcome::Failed("the v2 signature does not match the data field".into()),
+        },
+      });
+
+      let data = match &decoded {
+        Ok(map) => SignatureV2Data::from_cbor_map(map.clone()).map_err(|e| format!("{e:#}")),
+        Err(e) => Err(format!("{e:#}")),
+      };
+      let outcome = match data {
+        Ok(data) => {
+          let mismatches = v2_data_mismatches(entry, &data);
+          if mismatches.is_empty() {
+            CheckOutcome::Passed
+          } else {
+            CheckOutcome::Failed(mismatches.join("; "))
+          }
+        }
+        Err(e) => CheckOutcome::Failed(format!("the data field can't be decoded: {e}")),
+      };
+      checks.push(VerificationCheck {
+        kind: CheckKind::SignatureV2Data,
+        outcome,
+      });
+      checks.push(skipped(
+        CheckKind::SignatureV1,
+        "the v2 signature takes precedence",
+      ));
+    }
+    Ok(_) if options.requires_v2() => {
+      checks.push(VerificationCheck {
+        kind: CheckKind::SignatureV2,
+        outcome: CheckOutcome::Failed(
+          "the record has no v2 signature, which the validation options require".into(),
+        ),
+      });
+      checks.push(skipped(CheckKind::SignatureV2Data, "no v2 signature"));
+      checks.push(skipped(
+        CheckKind::SignatureV1,
+        "a v2 signature is required",
+      ));
+    }
+    Ok(public_key) => {
+      signature_version = Some(SignatureVersion::V1);
+      checks.push(skipped(
+        CheckKind::SignatureV2,
+        "the record has no v2 signature",
+      ));
+      checks.push(skipped(CheckKind::SignatureV2Data, "no v2 signature"));
+      checks.push(VerificationCheck {
+        kind: CheckKind::SignatureV1,
+        outcome: match validate_v1_signature(entry, public_key) {
+          Ok(()) => CheckOutcome::Passed,
+          Err(_) => CheckOutcome::Failed(
+            "the v1 signature does not match the value and validity fields".into(),
+          ),
+        },
+      });
+    }
+  }
+
+  checks.push(VerificationCheck {
+    kind: CheckKind::NotExpired,
+    outcome: check_not_expired(entry, options),
+  });
+
+  VerificationReport {
+    checks,
+    signature_version,
+    fields: record_fields(entry, decoded.ok().as_ref()),
+  }
+}
+
+fn skipped(kind: CheckKind, reason: &str) -> VerificationCheck {
+  VerificationCheck {
+    kind,
+    outcome: CheckOutcome::Skipped(reason.to_string()),
+  }
+}
+
+/// Resolves the public key with [entry_public_key], describing the failure instead of returning an error.
+fn check_public_key(entry: &IpnsEntry, name: &Name) -> Result<PublicKey, String> {
+  entry_public_key(entry, name).map_err(|err| {
+    err
+      .frames()
+      .find_map(|frame| match frame.kind() {
+        FrameKind::Attachment(AttachmentKind::Printable(description)) => {
+          Some(description.to_string())
+        }
+        _ => None,
+      })
+      .unwrap_or_else(|| format!("{err:#}"))
+  })
+}
+
+fn check_not_expired(entry: &IpnsEntry, options: &ValidationOptions) -> CheckOutcome {
+  if options.allows_expired() {
+    return CheckOutcome::Skipped("expired records are allowed by the validation options".into());
+  }
+  match parse_validity(&entry.validity) {
+    Ok(expired_at) if options.now() > expired_at => {
+      CheckOutcome::Failed(format!("the record expired at {}", expired_at.to_rfc3339()))
+    }
+    Ok(_) => CheckOutcome::Passed,
+    Err(_) => CheckOutcome::Failed(format!(
+      "the validity field is not an RFC-3339 timestamp: {}",
+      display_bytes(&entry.validity)
+    )),
+  }
+}
+
+fn record_fields(
+  entry: &IpnsEntry,
+  data: Option<&std::collections::BTreeMap<String, CborValue>>,
+) -> Vec<RecordField> {
+  // in the same order as RESERVED_V2_FIELDS
+  let protobuf = [
+    display_bytes(&entry.value),
+    display_bytes(&entry.validity),
+    entry.validity_type.to_string(),
+    entry.sequence.to_string(),
+    entry.ttl.to_string(),
+  ];
+  let mut fields: Vec<RecordField> = RESERVED_V2_FIELDS
+    .iter()
+    .zip(protobuf)
+    .map(|(key, protobuf)| RecordField {
+      name: key.to_string(),
+      protobuf: Some(protobuf),
+      data: data.and_then(|map| map.get(*key)).map(display_cbor),
+    })
+    .collect();
+
+  if let Some(map) = data {
+    fields.extend(
+      map
+        .iter()
+        .filter(|(key, _)| !RESERVED_V2_FIELDS.contains(&key.as_str()))
+        .map(|(key, value)| RecordField {
+          name: key.clone(),
+          protobuf: None,
+          data: Some(display_cbor(value)),
+        }),
+    );
+  }
+  fields
+}
+
+/// Renders bytes as a quoted string if they're valid UTF-8, or in hex otherwise.
+pub(super) fn display_bytes(bytes: &[u8]) -> String {
+  match std::str::from_utf8(bytes) {
+    Ok(s) => format!("{s:?}"),
+    Err(_) => {
+      let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+      format!("0x{hex}")
+    }
+  }
+}
+
+fn display_cbor(value: &CborValue) -> String {
+  match value {
+    CborValue::Bytes(bytes) => display_bytes(bytes),
+    CborValue::Text(s) => format!("{s:?}"),
+    CborValue::Integer(i) => i.to_string(),
+    other => format!("{other:?}"),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    ipns::{revision_to_ipns_entry, validate_ipns_entry, SigningOptions},
+    Revision, WritableName,
+  };
+
+  fn make_entry(name: &WritableName) -> IpnsEntry {
+    let rev = Revision::v0(&name.to_name(), "a value");
+    revision_to_ipns_entry(&rev, name.keypair(), &SigningOptions::default()).unwrap()
+  }
+
+  #[test]
+  fn valid_entry() {
+    let name = WritableName::new();
+    let entry = make_entry(&name);
+    let report = verification_report(&entry, &name.to_name(), &ValidationOptions::default());
+
+    assert!(report.is_valid());
+    assert_eq!(report.signature_version(), Some(SignatureVersion::V2));
+    assert!(matches!(
+      report.outcome(CheckKind::SignatureV1),
+      CheckOutcome::Skipped(_)
+    ));
+    assert!(report.fields().iter().all(RecordField::matches));
+    assert_eq!(report.fields()[0].protobuf.as_deref(), Some("\"a value\""));
+  }
+
+  #[test]
+  fn reports_mismatched_field() {
+    let name = WritableName::new();
+    let mut entry = make_entry(&name);
+    entry.sequence = 7;
+    let report = verification_report(&entry, &name.to_name(), &ValidationOptions::default());
+
+    assert!(!report.is_valid());
+    assert_eq!(
+      report.outcome(CheckKind::SignatureV2Data),
+      &CheckOutcome::Failed("Sequence is 7 in the record, but 0 in the signed data".into())
+    );
+    let sequence = report
+      .fields()
+      .iter()
+      .find(|f| f.name == "Sequence")
+      .unwrap();
+    assert!(!sequence.matches());
+    assert!(report.to_string().contains("Sequence: 7 | 0  <- mismatch"));
+  }
+
+  #[test]
+  fn describes_public_key_failures() {
+    let name = WritableName::new();
+    let other = WritableName::new().to_name();
+    let mut entry = make_entry(&name);
+    entry.pub_key = name.keypair().public().to_protobuf_encoding();
+
+    let report = verification_report(&entry, &other, &ValidationOptions::default());
+    let reason = format!("the pub_key field holds a key that does not belong to {other}");
+    assert_eq!(
+      report.outcome(CheckKind::PublicKey),
+      &CheckOutcome::Failed(reason.clone())
+    );
+    // the reason is the one validate_ipns_entry attaches to its error
+    let err = validate_ipns_entry(&entry, &other, &ValidationOptions::default()).unwrap_err();
+    assert!(format!("{err:#}").contains(&reason));
+
+    entry.pub_key = vec![1, 2, 3];
+    let report = verification_report(&entry, &name.to_name(), &ValidationOptions::default());
+    assert!(matches!(
+      report.outcome(CheckKind::PublicKey),
+      CheckOutcome::Failed(reason) if reason.starts_with("the pub_key field is not a valid public key: ")
+    ));
+  }
+
+  #[test]
+  fn agrees_with_validate_ipns_entry() {
+    let name = WritableName::new();
+    let other = WritableName::new();
+    let valid = make_entry(&name);
+
+    let mut v1_only = valid.clone();
+    v1_only.signature_v2 = vec![];
+    v1_only.data = vec![];
+    let mut bad_v1 = v1_only.clone();
+    bad_v1.value = b"another value".to_vec();
+    let mut bad_v2 = valid.clone();
+    bad_v2.signature_v2 = other.keypair().sign(b"nope").unwrap();
+
+    let strict = ValidationOptions::new().require_v2(true);
+    let expired =
+      ValidationOptions::new().at_time(chrono::Utc::now() + chrono::Duration::weeks(60));
+    for entry in [&valid, &v1_only, &bad_v1, &bad_v2] {
+      for options in [&ValidationOptions::default(), &strict, &expired] {
+        for n in [name.to_name(), other.to_name()] {
+          let report = verification_report(entry, &n, options);
+          let result = validate_ipns_entry(entry, &n, options);
+          assert_eq!(report.is_valid(), result.is_ok(), "{report}");
+        }
+      }
+    }
+  }
+}
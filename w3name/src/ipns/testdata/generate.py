@@ -0,0 +1,148 @@
+#!/usr/bin/env python3
+"""Writes the IPNS record fixtures in this directory.
+
+The records are built directly from the IPNS record spec (https://specs.ipfs.tech/ipns/ipns-record/),
+without using w3name, so the `reference_records` test checks this crate against an independent
+implementation. Ed25519 signatures are deterministic, so running this again gives identical files.
+
+Needs the `cryptography` package.
+"""
+
+from pathlib import Path
+
+from cryptography.hazmat.primitives.asymmetric.ed25519 import Ed25519PrivateKey
+from cryptography.hazmat.primitives.serialization import Encoding, PublicFormat
+
+# the ed25519 private key the fixtures are signed with; the test derives the same key with WritableName::from_seed
+SEED = bytes(range(1, 33))
+
+VALUE = b"/ipfs/bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi"
+VALIDITY = b"2033-05-18T03:33:20.000000000Z"
+TTL = 60 * 60 * 1_000_000_000
+
+
+def varint(n):
+    out = bytearray()
+    while True:
+        byte = n & 0x7F
+        n >>= 7
+        if n:
+            out.append(byte | 0x80)
+        else:
+            out.append(byte)
+            return bytes(out)
+
+
+def protobuf(fields):
+    """Encodes (field number, value) pairs, skipping proto3 default values like every protobuf encoder."""
+    out = bytearray()
+    for number, value in fields:
+        if not value:
+            continue
+        if isinstance(value, int):
+            out += varint(number << 3) + varint(value)
+        else:
+            out += varint(number << 3 | 2) + varint(len(value)) + value
+    return bytes(out)
+
+
+def cbor_head(major, n):
+    if n < 24:
+        return bytes([major << 5 | n])
+    for extra, size in ((24, 1), (25, 2), (26, 4), (27, 8)):
+        if n < 1 << (8 * size):
+            return bytes([major << 5 | extra]) + n.to_bytes(size, "big")
+    raise ValueError(n)
+
+
+def dag_cbor(value):
+    """Encodes unsigned integers, byte strings, text strings and maps with text keys as DAG-CBOR."""
+    if isinstance(value, int):
+        return cbor_head(0, value)
+    if isinstance(value, bytes):
+        return cbor_head(2, len(value)) + value
+    if isinstance(value, str):
+        return cbor_head(3, len(value.encode())) + value.encode()
+    if isinstance(value, dict):
+        # DAG-CBOR sorts map keys by length first, then bytewise
+        keys = sorted(value, key=lambda k: (len(k.encode()), k.encode()))
+        return cbor_head(5, len(keys)) + b"".join(dag_cbor(k) + dag_cbor(value[k]) for k in keys)
+    raise TypeError(value)
+
+
+def base36(data):
+    alphabet = "0123456789abcdefghijklmnopqrstuvwxyz"
+    n = int.from_bytes(data, "big")
+    digits = ""
+    while n:
+        n, digit = divmod(n, 36)
+        digits = alphabet[digit] + digits
+    leading_zeros = len(data) - len(data.lstrip(b"\0"))
+    return "k" + "0" * leading_zeros + digits
+
+
+def record(key, value, sequence, v1=True, extensions=None, data_value=None):
+    data = dag_cbor(
+        {
+            "Value": data_value or value,
+            "Validity": VALIDITY,
+            "ValidityType": 0,
+            "Sequence": sequence,
+            "TTL": TTL,
+            **(extensions or {}),
+        }
+    )
+    signature = key.sign(value + VALIDITY + b"EOL") if v1 else b""
+    signature_v2 = key.sign(b"ipns-signature:" + data)
+    return {
+        "value": value,
+        "signature": signature,
+        "validity": VALIDITY,
+        "sequence": sequence,
+        "signature_v2": signature_v2,
+        "data": data,
+    }
+
+
+def encode(rec):
+    return protobuf(
+        [
+            (1, rec["value"]),
+            (2, rec["signature"]),
+            (3, 0),
+            (4, rec["validity"]),
+            (5, rec["sequence"]),
+            (6, TTL),
+            # ed25519 keys are embedded in the name, so there's no pubKey field (7)
+            (8, rec["signature_v2"]),
+            (9, rec["data"]),
+        ]
+    )
+
+
+def main():
+    key = Ed25519PrivateKey.from_private_bytes(SEED)
+    public_key = key.public_key().public_bytes(Encoding.Raw, PublicFormat.Raw)
+    # libp2p protobuf public key, in an identity multihash, in a CIDv1 with the libp2p-key codec
+    key_pb = protobuf([(1, 1), (2, public_key)])
+    name = base36(bytes([0x01, 0x72, 0x00, len(key_pb)]) + key_pb)
+
+    broken_signature = record(key, VALUE, 1)
+    broken_signature["signature_v2"] = bytes([broken_signature["signature_v2"][0] ^ 1]) + broken_signature["signature_v2"][1:]
+
+    fixtures = {
+        "v1-v2": record(key, VALUE, 1),
+        "v2": record(key, VALUE, 2, v1=False),
+        "v1-v2-extension": record(key, VALUE, 3, extensions={"build": "abc123", "w3name/a": 7}),
+        "invalid-broken-signature-v2": broken_signature,
+        # the v1 signature covers the protobuf value, but the signed v2 data holds a different one
+        "invalid-broken-v1-value": record(key, b"/ipfs/another-value", 4, data_value=VALUE),
+    }
+
+    directory = Path(__file__).parent
+    for description, rec in fixtures.items():
+        (directory / f"{name}_{description}.ipns-record").write_bytes(encode(rec))
+
+
+if __name__ == "__main__":
+    main()
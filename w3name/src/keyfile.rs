@@ -0,0 +1,241 @@
+//! Password-encrypted containers for private keys.
+//!
+//! An encrypted key file has a fixed 64 byte header, followed by the encrypted key:
+//!
+//! | bytes | contents                                                     |
+//! |-------|--------------------------------------------------------------|
+//! | 10    | the magic string `w3name-key`                                |
+//! | 1     | format version, currently `1`                                |
+//! | 1     | key derivation function, currently `1` for Argon2id          |
+//! | 12    | Argon2id memory cost (KiB), iterations and parallelism, as big-endian `u32`s |
+//! | 16    | random salt for the key derivation function                  |
+//! | 24    | random XChaCha20-Poly1305 nonce                              |
+//! | rest  | the libp2p protobuf encoding of the key, encrypted with XChaCha20-Poly1305 |
+//!
+//! The whole header is used as associated data, so tampering with any of it makes decryption fail.
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+  aead::{Aead, KeyInit, Payload},
+  XChaCha20Poly1305, XNonce,
+};
+use error_stack::{report, Result};
+use rand::RngCore;
+use zeroize::Zeroizing;
+
+use crate::error::KeyFileError;
+
+const MAGIC: &[u8] = b"w3name-key";
+const VERSION: u8 = 1;
+const KDF_ARGON2ID: u8 = 1;
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 24;
+const HEADER_LENGTH: usize = MAGIC.len() + 2 + 12 + SALT_LENGTH + NONCE_LENGTH;
+
+// Limits on the cost of decrypting a key file we didn't write, so a malicious header can't make us spend
+// more than 1 GiB of memory or hang for hours. They're well above the defaults and common recommendations.
+const MAX_MEMORY_COST: u32 = 1024 * 1024;
+const MAX_ITERATIONS: u32 = 16;
+const MAX_PARALLELISM: u32 = 16;
+
+/// Argon2id cost parameters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KdfParams {
+  pub memory_kib: u32,
+  pub iterations: u32,
+  pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+  // the OWASP recommendation for Argon2id
+  fn default() -> Self {
+    KdfParams {
+      memory_kib: 19 * 1024,
+      iterations: 2,
+      parallelism: 1,
+    }
+  }
+}
+
+/// Returns true if `bytes` starts with the header of an encrypted key file.
+pub fn is_encrypted(bytes: &[u8]) -> bool {
+  bytes.starts_with(MAGIC)
+}
+
+/// Encrypts `key_bytes` with a key derived from `password`.
+pub fn encrypt(
+  key_bytes: &[u8],
+  password: &str,
+  params: KdfParams,
+) -> Result<Vec<u8>, KeyFileError> {
+  // don't write files that decrypt would refuse
+  check_limits(params)?;
+  let mut header = Vec::with_capacity(HEADER_LENGTH);
+  header.extend_from_slice(MAGIC);
+  header.extend_from_slice(&[VERSION, KDF_ARGON2ID]);
+  for n in [params.memory_kib, params.iterations, params.parallelism] {
+    header.extend_from_slice(&n.to_be_bytes());
+  }
+  let mut random = [0u8; SALT_LENGTH + NONCE_LENGTH];
+  rand::thread_rng().fill_bytes(&mut random);
+  header.extend_from_slice(&random);
+
+  let (salt, nonce) = random.split_at(SALT_LENGTH);
+  let cipher = cipher(password, salt, params)?;
+  let ciphertext = cipher
+    .encrypt(
+      XNonce::from_slice(nonce),
+      Payload {
+        msg: key_bytes,
+        aad: &header,
+      },
+    )
+    .map_err(|_| report!(KeyFileError).attach_printable("encryption failed"))?;
+
+  header.extend(ciphertext);
+  Ok(header)
+}
+
+/// Decrypts the key bytes from an encrypted key file created by [encrypt].
+pub fn decrypt(bytes: &[u8], password: &str) -> Result<Zeroizing<Vec<u8>>, KeyFileError> {
+  if !is_encrypted(bytes) || bytes.len() < HEADER_LENGTH {
+    return Err(report!(KeyFileError).attach_printable("not an encrypted key file"));
+  }
+  let (header, ciphertext) = bytes.split_at(HEADER_LENGTH);
+
+  let mut fields = &header[MAGIC.len()..];
+  let mut take = |n: usize| {
+    let (field, rest) = fields.split_at(n);
+    fields = rest;
+    field
+  };
+  let version = take(1)[0];
+  if version != VERSION {
+    return Err(
+      report!(KeyFileError).attach_printable(format!("unsupported key file version {version}")),
+    );
+  }
+  let kdf = take(1)[0];
+  if kdf != KDF_ARGON2ID {
+    return Err(
+      report!(KeyFileError).attach_printable(format!("unsupported key derivation function {kdf}")),
+    );
+  }
+  let mut next_u32 = || u32::from_be_bytes(take(4).try_into().unwrap());
+  let params = KdfParams {
+    memory_kib: next_u32(),
+    iterations: next_u32(),
+    parallelism: next_u32(),
+  };
+  check_limits(params)?;
+  let salt = take(SALT_LENGTH);
+  let nonce = take(NONCE_LENGTH);
+
+  cipher(password, salt, params)?
+    .decrypt(
+      XNonce::from_slice(nonce),
+      Payload {
+        msg: ciphertext,
+        aad: header,
+      },
+    )
+    .map(Zeroizing::new)
+    .map_err(|_| {
+      report!(KeyFileError).attach_printable("wrong password, or the key file is corrupted")
+    })
+}
+
+fn check_limits(params: KdfParams) -> Result<(), KeyFileError> {
+  for (param, value, limit) in [
+    ("memory cost (KiB)", params.memory_kib, MAX_MEMORY_COST),
+    ("iteration count", params.iterations, MAX_ITERATIONS),
+    ("parallelism", params.parallelism, MAX_PARALLELISM),
+  ] {
+    if value > limit {
+      return Err(report!(KeyFileError).attach_printable(format!(
+        "key derivation {param} is {value}, more than the limit of {limit}"
+      )));
+    }
+  }
+  Ok(())
+}
+
+fn cipher(
+  password: &str,
+  salt: &[u8],
+  params: KdfParams,
+) -> Result<XChaCha20Poly1305, KeyFileError> {
+  let params = Params::new(
+    params.memory_kib,
+    params.iterations,
+    params.parallelism,
+    Some(32),
+  )
+  .map_err(|e| report!(KeyFileError).attach_printable(format!("invalid Argon2 parameters: {e}")))?;
+
+  let mut key = Zeroizing::new([0u8; 32]);
+  Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+    .hash_password_into(password.as_bytes(), salt, &mut *key)
+    .map_err(|e| report!(KeyFileError).attach_printable(format!("key derivation failed: {e}")))?;
+  Ok(XChaCha20Poly1305::new(key.as_slice().into()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // cheap parameters, so the tests run quickly
+  const TEST_PARAMS: KdfParams = KdfParams {
+    memory_kib: 64,
+    iterations: 1,
+    parallelism: 1,
+  };
+
+  #[test]
+  fn roundtrip() {
+    let encrypted = encrypt(b"secret key", "hunter2", TEST_PARAMS).unwrap();
+    assert!(is_encrypted(&encrypted));
+    assert_eq!(encrypted.len(), HEADER_LENGTH + b"secret key".len() + 16);
+    assert_eq!(&decrypt(&encrypted, "hunter2").unwrap()[..], b"secret key");
+
+    // a new salt and nonce are used every time
+    assert_ne!(
+      encrypt(b"secret key", "hunter2", TEST_PARAMS).unwrap(),
+      encrypted
+    );
+  }
+
+  #[test]
+  fn rejects_wrong_password_and_tampering() {
+    let encrypted = encrypt(b"secret key", "hunter2", TEST_PARAMS).unwrap();
+    assert!(decrypt(&encrypted, "hunter3").is_err());
+
+    // flip a bit in the salt and in the nonce, which are authenticated along with the rest of the header
+    for offset in [MAGIC.len() + 14, HEADER_LENGTH - 1] {
+      let mut tampered = encrypted.clone();
+      tampered[offset] ^= 1;
+      let err = decrypt(&tampered, "hunter2").unwrap_err();
+      assert!(format!("{err:#}").contains("wrong password, or the key file is corrupted"));
+    }
+
+    let mut future_version = encrypted;
+    future_version[MAGIC.len()] = 2;
+    let err = decrypt(&future_version, "hunter2").unwrap_err();
+    assert!(format!("{err:#}").contains("unsupported key file version 2"));
+
+    assert!(decrypt(b"not a key file", "hunter2").is_err());
+  }
+
+  #[test]
+  fn rejects_excessive_costs() {
+    let encrypted = encrypt(b"secret key", "hunter2", TEST_PARAMS).unwrap();
+    // memory cost, iterations and parallelism are at offsets 0, 4 and 8 of the parameters
+    for offset in [0, 4, 8] {
+      let mut expensive = encrypted.clone();
+      let start = MAGIC.len() + 2 + offset;
+      expensive[start..start + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+      let err = decrypt(&expensive, "hunter2").unwrap_err();
+      assert!(format!("{err:#}").contains("more than the limit"));
+    }
+  }
+}
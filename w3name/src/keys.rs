@@ -0,0 +1,275 @@
+//! Encoding and decoding of private keys in the libp2p protobuf format.
+//!
+//! `libp2p-core` can only encode ed25519 keys this way, so the other key types are handled here,
+//! following the [libp2p key spec](https://github.com/libp2p/specs/blob/master/peer-ids/peer-ids.md#keys):
+//! secp256k1 keys are stored as the raw 32 byte secret, ECDSA keys as a DER-encoded SEC1 `ECPrivateKey`,
+//! and RSA keys as a DER-encoded PKCS#1 `RSAPrivateKey`.
+
+use error_stack::{report, IntoReport, Result, ResultExt};
+use libp2p_core::identity::{ecdsa, secp256k1, Keypair};
+use prost::Message;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::{
+  error::ProtobufError,
+  keys_pb::{KeyType, PrivateKey},
+};
+
+/// Encodes a [Keypair] into the libp2p protobuf format.
+///
+/// RSA keypairs can't be encoded, since the underlying `ring` crate doesn't export RSA private keys.
+/// Use [encode_rsa_private_key] instead.
+pub fn encode_keypair(keypair: &Keypair) -> Result<Zeroizing<Vec<u8>>, ProtobufError> {
+  let (key_type, data) = match keypair {
+    Keypair::Ed25519(_) => {
+      return keypair
+        .to_protobuf_encoding()
+        .map(Zeroizing::new)
+        .report()
+        .change_context(ProtobufError)
+    }
+    Keypair::Secp256k1(kp) => {
+      let secret = Zeroizing::new(kp.secret().to_bytes());
+      (KeyType::Secp256k1, secret.to_vec())
+    }
+    Keypair::Ecdsa(kp) => {
+      let secret = p256::SecretKey::from_be_bytes(&Zeroizing::new(kp.secret().to_bytes()))
+        .map_err(|_| report!(ProtobufError).attach_printable("invalid ECDSA secret key"))?;
+      let der = secret
+        .to_sec1_der()
+        .map_err(|_| report!(ProtobufError).attach_printable("unable to encode ECDSA key"))?;
+      (KeyType::Ecdsa, der.to_vec())
+    }
+    #[cfg(feature = "rsa")]
+    Keypair::Rsa(_) => {
+      return Err(report!(ProtobufError).attach_printable(
+        "RSA keys can only be encoded if they were generated or decoded by w3name",
+      ))
+    }
+  };
+
+  Ok(encode_private_key(key_type, data))
+}
+
+/// Encodes an [RsaPrivateKey] into the libp2p protobuf format.
+#[cfg(feature = "rsa")]
+pub fn encode_rsa_private_key(key: &RsaPrivateKey) -> Zeroizing<Vec<u8>> {
+  encode_private_key(KeyType::Rsa, key.pkcs1_der().to_vec())
+}
+
+fn encode_private_key(key_type: KeyType, data: Vec<u8>) -> Zeroizing<Vec<u8>> {
+  let pk = Zeroizing::new(PrivateKey {
+    r#type: key_type as i32,
+    data,
+  });
+  Zeroizing::new(pk.encode_to_vec())
+}
+
+/// Decodes a [Keypair] from the libp2p protobuf format.
+pub fn decode_keypair(bytes: &[u8]) -> Result<Keypair, ProtobufError> {
+  let mut pk = PrivateKey::decode(bytes)
+    .map(Zeroizing::new)
+    .report()
+    .change_context(ProtobufError)?;
+  let key_type = KeyType::from_i32(pk.r#type).ok_or_else(|| {
+    report!(ProtobufError).attach_printable(format!("unknown key type: {}", pk.r#type))
+  })?;
+
+  match key_type {
+    KeyType::Ed25519 => Keypair::from_protobuf_encoding(bytes)
+      .report()
+      .change_context(ProtobufError),
+    KeyType::Secp256k1 => {
+      let secret = secp256k1::SecretKey::from_bytes(&mut pk.data)
+        .report()
+        .change_context(ProtobufError)?;
+      Ok(Keypair::Secp256k1(secret.into()))
+    }
+    KeyType::Ecdsa => {
+      let secret = p256::SecretKey::from_sec1_der(&pk.data)
+        .map_err(|_| report!(ProtobufError).attach_printable("invalid ECDSA private key"))?;
+      let mut scalar = secret.to_be_bytes();
+      let secret = ecdsa::SecretKey::from_bytes(&scalar);
+      scalar.as_mut_slice().zeroize();
+      let secret = secret.report().change_context(ProtobufError)?;
+      Ok(Keypair::Ecdsa(secret.into()))
+    }
+    KeyType::Rsa => decode_rsa_keypair(&pk.data),
+  }
+}
+
+#[cfg(feature = "rsa")]
+fn decode_rsa_keypair(pkcs1_der: &[u8]) -> Result<Keypair, ProtobufError> {
+  RsaPrivateKey::from_pkcs1_der(pkcs1_der)
+    .keypair()
+    .change_context(ProtobufError)
+}
+
+#[cfg(not(feature = "rsa"))]
+fn decode_rsa_keypair(_pkcs1_der: &[u8]) -> Result<Keypair, ProtobufError> {
+  Err(report!(ProtobufError).attach_printable("RSA keys require the `rsa` feature"))
+}
+
+/// Decodes the [RsaPrivateKey] from the libp2p protobuf format, or returns `None` if `bytes` holds another type of key.
+#[cfg(feature = "rsa")]
+pub fn decode_rsa_private_key(bytes: &[u8]) -> Result<Option<RsaPrivateKey>, ProtobufError> {
+  let pk = PrivateKey::decode(bytes)
+    .map(Zeroizing::new)
+    .report()
+    .change_context(ProtobufError)?;
+  if pk.r#type != KeyType::Rsa as i32 {
+    return Ok(None);
+  }
+  Ok(Some(RsaPrivateKey::from_pkcs1_der(&pk.data)))
+}
+
+/// The private key of an RSA keypair, as a DER-encoded PKCS#1 `RSAPrivateKey` that is wiped from memory when dropped.
+///
+/// `ring`, which libp2p uses for RSA signatures, can't export private keys, so a
+/// [WritableName](crate::WritableName) keeps this next to the [Keypair] of an RSA key in order to encode it.
+#[cfg(feature = "rsa")]
+#[derive(Clone)]
+pub struct RsaPrivateKey(Zeroizing<Vec<u8>>);
+
+#[cfg(feature = "rsa")]
+impl RsaPrivateKey {
+  /// Generates a new RSA key with a modulus of `bits` bits.
+  pub fn generate(bits: usize) -> RsaPrivateKey {
+    use rsa::pkcs1::EncodeRsaPrivateKey;
+
+    let key = rsa::RsaPrivateKey::new(&mut rand::thread_rng(), bits)
+      .expect("RSA key generation only fails for invalid key sizes");
+    let der = key
+      .to_pkcs1_der()
+      .expect("generated RSA keys can be encoded");
+    RsaPrivateKey::from_pkcs1_der(der.as_bytes())
+  }
+
+  /// Wraps a DER-encoded PKCS#1 `RSAPrivateKey`. The key is only checked by [keypair](Self::keypair).
+  pub fn from_pkcs1_der(der: &[u8]) -> RsaPrivateKey {
+    RsaPrivateKey(Zeroizing::new(der.to_vec()))
+  }
+
+  pub fn pkcs1_der(&self) -> &[u8] {
+    &self.0
+  }
+
+  /// Encodes the key as a DER-encoded PKCS#8 `PrivateKeyInfo`.
+  pub fn to_pkcs8_der(&self) -> Result<Zeroizing<Vec<u8>>, ProtobufError> {
+    use pkcs8::{der::Encode, AlgorithmIdentifier, ObjectIdentifier, PrivateKeyInfo};
+
+    const RSA_ENCRYPTION: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.1");
+    let info = PrivateKeyInfo::new(
+      AlgorithmIdentifier {
+        oid: RSA_ENCRYPTION,
+        parameters: Some(pkcs8::der::asn1::Null.into()),
+      },
+      self.pkcs1_der(),
+    );
+    info
+      .to_vec()
+      .map(Zeroizing::new)
+      .map_err(|_| report!(ProtobufError).attach_printable("invalid RSA private key"))
+  }
+
+  /// Returns the libp2p [Keypair] for signing with this key, or an error if the key is invalid.
+  pub fn keypair(&self) -> Result<Keypair, ProtobufError> {
+    // `ring` only reads PKCS#8
+    let mut pkcs8_der = self.to_pkcs8_der()?;
+    Keypair::rsa_from_pkcs8(&mut pkcs8_der)
+      .report()
+      .change_context(ProtobufError)
+  }
+}
+
+#[cfg(feature = "rsa")]
+impl std::fmt::Debug for RsaPrivateKey {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str("RsaPrivateKey(..)")
+  }
+}
+
+// `PrivateKey` is generated by prost, so it can't derive `Zeroize`.
+impl Zeroize for PrivateKey {
+  fn zeroize(&mut self) {
+    self.r#type.zeroize();
+    self.data.zeroize();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn round_trip_all_key_types() {
+    for keypair in [
+      Keypair::generate_ed25519(),
+      Keypair::generate_secp256k1(),
+      Keypair::generate_ecdsa(),
+    ] {
+      let bytes = encode_keypair(&keypair).unwrap();
+      let decoded = decode_keypair(&bytes).unwrap();
+      assert_eq!(keypair.public(), decoded.public());
+    }
+  }
+
+  /// A 2048-bit RSA key in the libp2p protobuf format, made with `openssl genrsa -traditional`.
+  const RSA_KEY: &[u8] = include_bytes!("testdata/rsa-2048.key");
+
+  #[cfg(feature = "rsa")]
+  #[test]
+  fn decode_rsa_key() {
+    use crate::{IpnsRecord, Name, Revision, WritableName};
+
+    let keypair = decode_keypair(RSA_KEY).unwrap();
+    assert!(matches!(keypair, Keypair::Rsa(_)));
+    // the private key can't be read back from a libp2p keypair
+    assert!(encode_keypair(&keypair).is_err());
+    assert!(WritableName::from(keypair).encode().is_err());
+
+    // a decoded WritableName keeps the key, so it encodes to the same bytes
+    let name = WritableName::decode(RSA_KEY).unwrap();
+    assert_eq!(&name.encode().unwrap()[..], RSA_KEY);
+
+    // RSA names are hashed, so records carry the key and verify against the parsed name
+    let parsed = Name::parse(name.to_string()).unwrap();
+    assert!(parsed.public_key().is_none());
+    let record = IpnsRecord::new(&name, &Revision::v0(&parsed, "a value")).unwrap();
+    assert!(!record.public_key_bytes().is_empty());
+    record.verify(&parsed).unwrap();
+  }
+
+  #[cfg(feature = "rsa")]
+  #[test]
+  fn generate_and_export_rsa_key() {
+    use crate::WritableName;
+
+    let name = WritableName::new_rsa();
+    assert!(matches!(name.keypair(), Keypair::Rsa(_)));
+
+    let bytes = name.encode().unwrap();
+    let pk = PrivateKey::decode(&bytes[..]).unwrap();
+    assert_eq!(pk.r#type, KeyType::Rsa as i32);
+    assert_eq!(WritableName::decode(&bytes).unwrap(), name);
+
+    let pem = name.to_pkcs8_pem().unwrap();
+    let imported = WritableName::from_pkcs8_pem(&pem).unwrap();
+    assert_eq!(imported, name);
+    assert_eq!(imported.encode().unwrap(), bytes);
+  }
+
+  #[cfg(not(feature = "rsa"))]
+  #[test]
+  fn rsa_keys_need_the_feature() {
+    assert!(decode_keypair(RSA_KEY).is_err());
+  }
+
+  #[test]
+  fn ecdsa_key_is_sec1_der() {
+    let bytes = encode_keypair(&Keypair::generate_ecdsa()).unwrap();
+    let pk = PrivateKey::decode(&bytes[..]).unwrap();
+    assert_eq!(pk.r#type, KeyType::Ecdsa as i32);
+    assert!(p256::SecretKey::from_sec1_der(&pk.data).is_ok());
+  }
+}
@@ -0,0 +1,613 @@
+use std::{
+  collections::BTreeMap,
+  fs,
+  io::{self, Write},
+  path::{Path, PathBuf},
+  sync::Mutex,
+};
+
+use error_stack::{report, IntoReport, Report, Result, ResultExt};
+use zeroize::Zeroizing;
+
+use crate::{
+  error::{KeyNotFound, KeystoreError},
+  Name, WritableName,
+};
+
+const MAX_LABEL_LEN: usize = 64;
+const KEY_FILE_EXTENSION: &str = "key";
+
+/// The label and [Name] of a key in a [Keystore].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeystoreEntry {
+  label: String,
+  name: Name,
+}
+
+impl KeystoreEntry {
+  /// Returns the label that the key is stored under.
+  pub fn label(&self) -> &str {
+    &self.label
+  }
+
+  /// Returns the [Name] of the key.
+  pub fn name(&self) -> &Name {
+    &self.name
+  }
+}
+
+/// A `Keystore` keeps [WritableName]s under human-readable labels, like `"blog"` or `"staging-site"`.
+///
+/// Keys can be looked up either by their label, or by the string form of their [Name]. To keep lookups
+/// unambiguous, each key can only be stored once, and labels that are valid names themselves are rejected.
+/// Labels are 1 to 64 ASCII letters, digits, `-`, `_` and `.`, and can't start with a `.`.
+///
+/// This crate has two implementations: [FileKeystore] keeps keys in a directory on disk, and
+/// [MemoryKeystore] keeps them in memory, which is mostly useful for tests.
+///
+/// ## Example
+///
+/// ```rust
+/// # fn main() -> error_stack::Result<(), w3name::error::KeystoreError> {
+/// use w3name::{Keystore, MemoryKeystore, WritableName};
+///
+/// let keystore = MemoryKeystore::new();
+/// let name = WritableName::new();
+/// keystore.put("blog", &name)?;
+///
+/// // keys can be loaded by label or by name
+/// assert_eq!(WritableName::from_keystore(&keystore, "blog")?, name);
+/// assert_eq!(keystore.get(&name.to_string())?, name);
+///
+/// keystore.rename("blog", "old-blog")?;
+/// assert_eq!(keystore.list()?[0].label(), "old-blog");
+/// # Ok(())
+/// # }
+/// ```
+pub trait Keystore {
+  /// Returns the labels and names of all keys in the store, sorted by label.
+  fn list(&self) -> Result<Vec<KeystoreEntry>, KeystoreError>;
+
+  /// Returns the key stored under `key`, which is either a label or a name.
+  ///
+  /// Fails with a [KeyNotFound] error if there is no such key.
+  fn get(&self, key: &str) -> Result<WritableName, KeystoreError>;
+
+  /// Stores `name` under `label`. Fails if the label is invalid or taken, or if the key is already stored.
+  fn put(&self, label: &str, name: &WritableName) -> Result<(), KeystoreError>;
+
+  /// Deletes the key stored under `key`, which is either a label or a name.
+  fn delete(&self, key: &str) -> Result<(), KeystoreError>;
+
+  /// Moves the key stored under `key`, which is either a label or a name, to `new_label`.
+  fn rename(&self, key: &str, new_label: &str) -> Result<(), KeystoreError>;
+}
+
+/// A [Keystore] that keeps each key in a `<label>.key` file in a directory.
+///
+/// The key files use the same formats as the `w3name create` command, so existing key files can be copied into
+/// the directory. The directory is only accessible by the current user, and key files are only readable and
+/// writable by their owner.
+///
+/// By default, keys are stored unencrypted, like [WritableName::encode] writes them. A keystore opened
+/// [with a password](Self::with_password) encrypts the keys it stores with [WritableName::encrypt], and can read
+/// both encrypted and unencrypted key files. Files that can't be decoded, e.g. encrypted ones without the right
+/// password, are left out of [list](Keystore::list), [get](Keystore::get) fails for them with the reason, and
+/// their labels can't be reused.
+///
+/// ```rust
+/// # fn main() -> error_stack::Result<(), w3name::error::KeystoreError> {
+/// # let tmp = tempfile::tempdir().unwrap();
+/// # let dir = tmp.path().join("keys");
+/// use w3name::{FileKeystore, Keystore, WritableName};
+///
+/// let keystore = FileKeystore::open(&dir)?.with_password("correct horse battery staple");
+/// let name = WritableName::new();
+/// keystore.put("blog", &name)?;
+/// assert_eq!(keystore.get("blog")?, name);
+///
+/// // without the password, the key can't be read
+/// assert!(FileKeystore::open(&dir)?.get("blog").is_err());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Use [open_default](Self::open_default) to share keys between applications, or [open](Self::open) for a
+/// directory of your own.
+#[derive(Clone)]
+pub struct FileKeystore {
+  dir: PathBuf,
+  password: Option<Zeroizing<String>>,
+}
+
+impl std::fmt::Debug for FileKeystore {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("FileKeystore")
+      .field("dir", &self.dir)
+      .field("encrypted", &self.password.is_some())
+      .finish()
+  }
+}
+
+impl FileKeystore {
+  /// Opens the keystore in `dir`, creating the directory if it doesn't exist.
+  ///
+  /// On unix, if an existing directory can be accessed by other users, it is changed to be accessible only by its
+  /// owner, and opening fails if that isn't possible.
+  pub fn open<P: AsRef<Path>>(dir: P) -> Result<FileKeystore, KeystoreError> {
+    let dir = dir.as_ref().to_path_buf();
+    if dir.is_dir() {
+      restrict_dir(&dir)
+        .report()
+        .change_context(KeystoreError)
+        .attach_printable_lazy(|| format!("unable to restrict access to {}", dir.display()))?;
+    } else {
+      create_private_dir(&dir)
+        .report()
+        .change_context(KeystoreError)
+        .attach_printable_lazy(|| format!("unable to create {}", dir.display()))?;
+    }
+    Ok(FileKeystore {
+      dir,
+      password: None,
+    })
+  }
+
+  /// Encrypts the keys that are [put](Keystore::put) into the store with `password`, and uses it to decrypt
+  /// encrypted key files.
+  ///
+  /// Encrypted keys are decrypted every time they are read, including by [list](Keystore::list) and
+  /// [put](Keystore::put), which check every key in the store. This is deliberately slow, so a password-protected
+  /// store is best kept to a handful of keys.
+  pub fn with_password(mut self, password: &str) -> FileKeystore {
+    self.password = Some(Zeroizing::new(password.to_string()));
+    self
+  }
+
+  /// Opens the keystore in the [default directory](Self::default_dir), creating it if it doesn't exist.
+  pub fn open_default() -> Result<FileKeystore, KeystoreError> {
+    let dir = Self::default_dir().ok_or_else(|| {
+      report!(KeystoreError).attach_printable("unable to find the user's data directory")
+    })?;
+    Self::open(dir)
+  }
+
+  /// Returns the default keystore directory, which is `w3name/keys` in the user's data directory:
+  ///
+  /// - `$XDG_DATA_HOME/w3name/keys` or `~/.local/share/w3name/keys` on Linux
+  /// - `~/Library/Application Support/w3name/keys` on macOS
+  /// - `%APPDATA%\w3name\keys` on Windows
+  ///
+  /// Returns `None` if the data directory can't be determined.
+  pub fn default_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("w3name").join("keys"))
+  }
+
+  /// Returns the directory that this keystore keeps its key files in.
+  pub fn dir(&self) -> &Path {
+    &self.dir
+  }
+
+  fn path(&self, label: &str) -> PathBuf {
+    self.dir.join(format!("{label}.{KEY_FILE_EXTENSION}"))
+  }
+
+  fn read(&self, label: &str) -> Result<WritableName, KeystoreError> {
+    let path = self.path(label);
+    let bytes = match fs::read(&path) {
+      Ok(bytes) => Zeroizing::new(bytes),
+      Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(not_found(label)),
+      Err(e) => {
+        return Err(
+          report!(e)
+            .change_context(KeystoreError)
+            .attach_printable(format!("unable to read {}", path.display())),
+        )
+      }
+    };
+    if WritableName::is_encrypted(&bytes) {
+      let password = self.password.as_ref().ok_or_else(|| {
+        report!(KeystoreError).attach_printable(format!(
+          "{} is encrypted, but the keystore has no password",
+          path.display()
+        ))
+      })?;
+      return WritableName::decrypt(&bytes, password)
+        .change_context(KeystoreError)
+        .attach_printable_lazy(|| format!("unable to decrypt {}", path.display()));
+    }
+    WritableName::decode(&bytes)
+      .change_context(KeystoreError)
+      .attach_printable_lazy(|| format!("invalid key file {}", path.display()))
+  }
+
+  /// Checks that there is no file for `label`, including files that [list](Keystore::list) skips.
+  fn check_unused(&self, label: &str) -> Result<(), KeystoreError> {
+    if self.path(label).exists() {
+      return Err(
+        report!(KeystoreError).attach_printable(format!("label {label:?} is already in use")),
+      );
+    }
+    Ok(())
+  }
+
+  /// Returns the label of the key that `key` refers to.
+  fn find(&self, key: &str) -> Result<String, KeystoreError> {
+    if Name::parse(key).is_ok() {
+      return find_label(&self.list()?, key);
+    }
+    if is_valid_label(key) && self.path(key).is_file() {
+      Ok(key.to_string())
+    } else {
+      Err(not_found(key))
+    }
+  }
+}
+
+impl Keystore for FileKeystore {
+  fn list(&self) -> Result<Vec<KeystoreEntry>, KeystoreError> {
+    let dir_entries = fs::read_dir(&self.dir)
+      .report()
+      .change_context(KeystoreError)
+      .attach_printable_lazy(|| format!("unable to read {}", self.dir.display()))?;
+
+    let mut entries = vec![];
+    for dir_entry in dir_entries {
+      let path = dir_entry.report().change_context(KeystoreError)?.path();
+      if path.extension().and_then(|ext| ext.to_str()) != Some(KEY_FILE_EXTENSION) {
+        continue;
+      }
+      let label = match path.file_stem().and_then(|stem| stem.to_str()) {
+        Some(label) if is_valid_label(label) => label.to_string(),
+        _ => continue,
+      };
+      // one unreadable file, e.g. an encrypted one, shouldn't make the whole store unusable
+      if let Ok(name) = self.read(&label) {
+        entries.push(KeystoreEntry {
+          label,
+          name: name.to_name(),
+        });
+      }
+    }
+    entries.sort_by(|a, b| a.label.cmp(&b.label));
+    Ok(entries)
+  }
+
+  fn get(&self, key: &str) -> Result<WritableName, KeystoreError> {
+    self.read(&self.find(key)?)
+  }
+
+  fn put(&self, label: &str, name: &WritableName) -> Result<(), KeystoreError> {
+    check_new_entry(&self.list()?, label, Some(&name.to_name()))?;
+    self.check_unused(label)?;
+
+    let path = self.path(label);
+    let bytes = match &self.password {
+      Some(password) => Zeroizing::new(name.encrypt(password).change_context(KeystoreError)?),
+      None => name.encode().change_context(KeystoreError)?,
+    };
+    write_private_file(&path, &bytes)
+      .report()
+      .change_context(KeystoreError)
+      .attach_printable_lazy(|| format!("unable to write {}", path.display()))
+  }
+
+  fn delete(&self, key: &str) -> Result<(), KeystoreError> {
+    let path = self.path(&self.find(key)?);
+    fs::remove_file(&path)
+      .report()
+      .change_context(KeystoreError)
+      .attach_printable_lazy(|| format!("unable to delete {}", path.display()))
+  }
+
+  fn rename(&self, key: &str, new_label: &str) -> Result<(), KeystoreError> {
+    let label = self.find(key)?;
+    if label == new_label {
+      return Ok(());
+    }
+    check_new_entry(&self.list()?, new_label, None)?;
+    // fs::rename would replace a file that list skipped
+    self.check_unused(new_label)?;
+
+    let (from, to) = (self.path(&label), self.path(new_label));
+    fs::rename(&from, &to)
+      .report()
+      .change_context(KeystoreError)
+      .attach_printable_lazy(|| format!("unable to move {} to {}", from.display(), to.display()))
+  }
+}
+
+/// A [Keystore] that keeps keys in memory, for tests and short-lived tools.
+#[derive(Debug, Default)]
+pub struct MemoryKeystore {
+  keys: Mutex<BTreeMap<String, WritableName>>,
+}
+
+impl MemoryKeystore {
+  /// Creates an empty `MemoryKeystore`.
+  pub fn new() -> MemoryKeystore {
+    MemoryKeystore::default()
+  }
+
+  fn keys(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, WritableName>> {
+    // the map is never left in an inconsistent state, so a panic in another thread doesn't matter
+    self
+      .keys
+      .lock()
+      .unwrap_or_else(|poisoned| poisoned.into_inner())
+  }
+}
+
+impl Keystore for MemoryKeystore {
+  fn list(&self) -> Result<Vec<KeystoreEntry>, KeystoreError> {
+    Ok(entries(&self.keys()))
+  }
+
+  fn get(&self, key: &str) -> Result<WritableName, KeystoreError> {
+    let keys = self.keys();
+    let label = find_label(&entries(&keys), key)?;
+    Ok(keys[&label].clone())
+  }
+
+  fn put(&self, label: &str, name: &WritableName) -> Result<(), KeystoreError> {
+    let mut keys = self.keys();
+    check_new_entry(&entries(&keys), label, Some(&name.to_name()))?;
+    keys.insert(label.to_string(), name.clone());
+    Ok(())
+  }
+
+  fn delete(&self, key: &str) -> Result<(), KeystoreError> {
+    let mut keys = self.keys();
+    let label = find_label(&entries(&keys), key)?;
+    keys.remove(&label);
+    Ok(())
+  }
+
+  fn rename(&self, key: &str, new_label: &str) -> Result<(), KeystoreError> {
+    let mut keys = self.keys();
+    let label = find_label(&entries(&keys), key)?;
+    if label == new_label {
+      return Ok(());
+    }
+    check_new_entry(&entries(&keys), new_label, None)?;
+    let name = keys.remove(&label).expect("label was just found");
+    keys.insert(new_label.to_string(), name);
+    Ok(())
+  }
+}
+
+fn entries(keys: &BTreeMap<String, WritableName>) -> Vec<KeystoreEntry> {
+  keys
+    .iter()
+    .map(|(label, name)| KeystoreEntry {
+      label: label.clone(),
+      name: name.to_name(),
+    })
+    .collect()
+}
+
+/// Returns the label of the entry that `key` refers to, which is either a label or a name.
+fn find_label(entries: &[KeystoreEntry], key: &str) -> Result<String, KeystoreError> {
+  let found = match Name::parse(key) {
+    Ok(name) => entries.iter().find(|entry| entry.name == name),
+    Err(_) => entries.iter().find(|entry| entry.label == key),
+  };
+  found
+    .map(|entry| entry.label.clone())
+    .ok_or_else(|| not_found(key))
+}
+
+/// Checks that `label` is valid and unused, and that `name` (if given) isn't stored under another label.
+fn check_new_entry(
+  entries: &[KeystoreEntry],
+  label: &str,
+  name: Option<&Name>,
+) -> Result<(), KeystoreError> {
+  if !is_valid_label(label) {
+    return Err(report!(KeystoreError).attach_printable(format!(
+      "invalid label {label:?}: labels must be 1 to {MAX_LABEL_LEN} letters, digits, '-', '_' or '.', and can't be names"
+    )));
+  }
+  if entries.iter().any(|entry| entry.label == label) {
+    return Err(
+      report!(KeystoreError).attach_printable(format!("label {label:?} is already in use")),
+    );
+  }
+  if let Some(existing) = entries.iter().find(|entry| Some(&entry.name) == name) {
+    return Err(report!(KeystoreError).attach_printable(format!(
+      "key {} is already stored as {:?}",
+      existing.name, existing.label
+    )));
+  }
+  Ok(())
+}
+
+fn is_valid_label(label: &str) -> bool {
+  !label.is_empty()
+    && label.len() <= MAX_LABEL_LEN
+    && !label.starts_with('.')
+    && label
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    && Name::parse(label).is_err()
+}
+
+fn not_found(key: &str) -> Report<KeystoreError> {
+  report!(KeyNotFound {
+    key: key.to_string()
+  })
+  .change_context(KeystoreError)
+}
+
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+  let mut builder = fs::DirBuilder::new();
+  builder.recursive(true);
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::DirBuilderExt;
+    builder.mode(0o700);
+  }
+  builder.create(dir)
+}
+
+/// Removes access for other users from an existing directory.
+fn restrict_dir(dir: &Path) -> io::Result<()> {
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::PermissionsExt;
+    let mut permissions = fs::metadata(dir)?.permissions();
+    if permissions.mode() & 0o077 != 0 {
+      permissions.set_mode(permissions.mode() & 0o700);
+      fs::set_permissions(dir, permissions)?;
+    }
+  }
+  #[cfg(not(unix))]
+  let _ = dir;
+  Ok(())
+}
+
+/// Writes a new file that only the current user can read. Fails if the file already exists.
+fn write_private_file(path: &Path, bytes: &[u8]) -> io::Result<()> {
+  let mut options = fs::OpenOptions::new();
+  options.write(true).create_new(true);
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::OpenOptionsExt;
+    options.mode(0o600);
+  }
+
+  let mut file = options.open(path)?;
+  if let Err(e) = file.write_all(bytes).and_then(|_| file.sync_all()) {
+    let _ = fs::remove_file(path);
+    return Err(e);
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn exercise(keystore: &dyn Keystore) {
+    let blog = WritableName::new();
+    let site = WritableName::new_secp256k1();
+    keystore.put("site", &site).unwrap();
+    keystore.put("blog", &blog).unwrap();
+
+    let labels: Vec<_> = keystore
+      .list()
+      .unwrap()
+      .iter()
+      .map(|entry| entry.label().to_string())
+      .collect();
+    assert_eq!(labels, ["blog", "site"]);
+    assert_eq!(keystore.get("blog").unwrap(), blog);
+    assert_eq!(keystore.get(&site.to_string()).unwrap(), site);
+
+    // labels and keys can only be used once
+    assert!(keystore.put("blog", &WritableName::new()).is_err());
+    assert!(keystore.put("blog-again", &blog).is_err());
+    assert!(keystore.put("../escape", &WritableName::new()).is_err());
+    assert!(keystore
+      .put(&WritableName::new().to_string(), &WritableName::new())
+      .is_err());
+
+    keystore.rename(&blog.to_string(), "old-blog").unwrap();
+    assert!(keystore.rename("old-blog", "site").is_err());
+    assert_eq!(
+      WritableName::from_keystore(keystore, "old-blog").unwrap(),
+      blog
+    );
+
+    keystore.delete("old-blog").unwrap();
+    let err = keystore.get("old-blog").unwrap_err();
+    assert_eq!(err.downcast_ref::<KeyNotFound>().unwrap().key, "old-blog");
+    assert!(keystore.delete(&blog.to_string()).is_err());
+    assert_eq!(keystore.list().unwrap().len(), 1);
+  }
+
+  #[test]
+  fn memory_keystore() {
+    exercise(&MemoryKeystore::new());
+  }
+
+  #[test]
+  fn file_keystore() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path().join("keys");
+    let keystore = FileKeystore::open(&dir).unwrap();
+    exercise(&keystore);
+
+    // files that aren't keys are ignored
+    fs::write(dir.join("notes.txt"), "hello").unwrap();
+    assert_eq!(keystore.list().unwrap().len(), 1);
+
+    // as are key files that can't be decoded, but their labels stay taken
+    let encrypted = WritableName::new().encrypt("password").unwrap();
+    fs::write(dir.join("locked.key"), &encrypted).unwrap();
+    fs::write(dir.join("corrupt.key"), "not a key").unwrap();
+    assert_eq!(keystore.list().unwrap().len(), 1);
+    let err = keystore.get("locked").unwrap_err();
+    assert!(format!("{err:?}").contains("encrypted"));
+    assert!(keystore.get("corrupt").is_err());
+    assert!(keystore.put("locked", &WritableName::new()).is_err());
+    assert!(keystore.rename("site", "corrupt").is_err());
+    assert_eq!(fs::read(dir.join("locked.key")).unwrap(), encrypted);
+    assert_eq!(fs::read(dir.join("corrupt.key")).unwrap(), b"not a key");
+    keystore.put("another", &WritableName::new()).unwrap();
+    assert_eq!(keystore.list().unwrap().len(), 2);
+
+    #[cfg(unix)]
+    {
+      use std::os::unix::fs::PermissionsExt;
+      let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+      assert_eq!(mode(&dir), 0o700);
+      assert_eq!(mode(&dir.join("site.key")), 0o600);
+    }
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn file_keystore_restricts_existing_dir() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path().join("keys");
+    fs::create_dir(&dir).unwrap();
+    fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let keystore = FileKeystore::open(&dir).unwrap();
+    let mode = fs::metadata(&dir).unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode, 0o700);
+    keystore.put("blog", &WritableName::new()).unwrap();
+  }
+
+  #[test]
+  fn file_keystore_with_password() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path().join("keys");
+    let keystore = FileKeystore::open(&dir).unwrap().with_password("password");
+
+    let blog = WritableName::new();
+    keystore.put("blog", &blog).unwrap();
+    let bytes = fs::read(dir.join("blog.key")).unwrap();
+    assert!(WritableName::is_encrypted(&bytes));
+    assert_eq!(WritableName::decrypt(&bytes, "password").unwrap(), blog);
+    assert_eq!(keystore.get(&blog.to_string()).unwrap(), blog);
+
+    // unencrypted key files can still be read
+    let site = WritableName::new();
+    fs::write(dir.join("site.key"), &*site.encode().unwrap()).unwrap();
+    assert_eq!(keystore.get("site").unwrap(), site);
+    assert_eq!(keystore.list().unwrap().len(), 2);
+
+    // without the right password, encrypted keys are skipped and can't be loaded
+    let without_password = FileKeystore::open(&dir).unwrap();
+    let err = without_password.get("blog").unwrap_err();
+    assert!(format!("{err:?}").contains("no password"));
+    let wrong_password = FileKeystore::open(&dir).unwrap().with_password("wrong");
+    assert!(wrong_password.get("blog").is_err());
+    assert_eq!(wrong_password.list().unwrap().len(), 1);
+    assert!(!format!("{wrong_password:?}").contains("wrong"));
+  }
+}
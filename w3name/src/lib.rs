@@ -12,16 +12,35 @@
 //! The `w3name` crate provides a few types for working with names and name records:
 //! - [Name] is a representation of a name identifier. It contains the public verification key.
 //!   `Name`s can be used to fetch and verify the latest published value for a name record.
+//!   A `Name` can also be converted to and from a [did:key](https://w3c-ccg.github.io/did-method-key/) identifier.
 //! - [WritableName] contains a private key that can be used to sign and publish name records.
-//! - [Revision] represents an unsigned name record. It contains a string value and some metadata (sequence number, expiration date, etc).
+//!   Keys can be random, or derived deterministically from a seed or BIP-39 mnemonic with [MasterKey].
+//!   To get a name that's easier to recognize, search for one that ends with a pattern with [VanitySearch].
+//!   Applications that manage many names can keep them under human-readable labels in a [Keystore].
+//! - [Revision] represents an unsigned name record. It contains a value (usually a string, but any bytes are allowed) and some metadata (sequence number, expiration date, etc).
+//! - [Value] is a validated `/ipfs/<cid>` or `/ipns/<name>` content path, which can be used as the value of a [Revision].
+//! - [IpnsRecord] is a signed name record, created by signing a [Revision] with a [WritableName].
+//!   Records can be encoded to and decoded from the standard IPNS protobuf format and verified against a [Name].
+//!   To find out why a record fails verification, create a [VerificationReport] with [IpnsRecord::verification_report].
+//! - [Signer] is implemented by [WritableName], and can be implemented for keys that live outside of this process,
+//!   like hardware tokens or a separate signing service. Records can be signed and published with any `Signer`.
+//! - [Succession] is a statement, signed by a retired name's key, that points followers at a new name.
+//!   See [W3NameClient::rotate] and [W3NameClient::resolve_succession].
 //!
 //! The [W3NameClient] type provides a [reqwest](https://docs.rs/reqwest/latest/reqwest/)-based HTTP client
 //! for the w3name service. Using the client, you can [resolve](W3NameClient::resolve) the value for a [Name] and/or
-//! [publish](W3NameClient::publish) a new [Revision] for a [WritableName].
+//! [publish](W3NameClient::publish) a new [Revision] for a [WritableName]. Values that point at other names can be
+//! followed with [resolve_recursive](W3NameClient::resolve_recursive).
 //!
 //! Note that the client requires a [tokio](https://tokio.rs) runtime, as it uses the async reqwest implementation.
 //! For a real-world example of using the client, see [w3name-cli](https://crates.io/crates/w3name-cli).
 //!
+//! ## Private keys
+//!
+//! Methods that return private key material, like [WritableName::encode], wrap it in [Zeroizing], which overwrites
+//! the memory when it's dropped. Buffers that hold private keys while they're decoded, encrypted or derived are
+//! wiped the same way.
+//!
 //! ## Errors
 //!
 //! This crate uses the [error-stack](https://docs.rs/error-stack/latest/error_stack/) library for error handling,
@@ -33,17 +52,48 @@
 //! [`Report::current_context()`](https://docs.rs/error-stack/latest/error_stack/struct.Report.html#method.current_context).
 
 mod client;
+mod clock;
+mod derive;
+mod did;
 pub mod error;
 mod hash;
 mod ipns;
+mod keyfile;
+mod keys;
+mod keystore;
 mod name;
+mod pkcs;
+mod resolution;
 mod revision;
+mod signer;
+mod succession;
+mod value;
+mod vanity;
 
 // Include the `ipns_pb` module, which is generated from ipns/ipns_pb.proto.
 mod ipns_pb {
   include!(concat!(env!("OUT_DIR"), "/ipns_pb.rs"));
 }
 
+// Include the `keys_pb` module, which is generated from keys_pb.proto.
+mod keys_pb {
+  include!(concat!(env!("OUT_DIR"), "/keys_pb.rs"));
+}
+
+pub use async_trait::async_trait;
 pub use client::W3NameClient;
-pub use name::{Name, WritableName};
-pub use revision::Revision;
+pub use clock::{Clock, FixedClock, SystemClock};
+pub use derive::MasterKey;
+pub use ipns::{
+  CheckKind, CheckOutcome, IpnsRecord, RecordField, SignatureVersion, SigningOptions,
+  ValidationOptions, VerificationCheck, VerificationReport, MAX_RECORD_SIZE,
+};
+pub use keystore::{FileKeystore, Keystore, KeystoreEntry, MemoryKeystore};
+pub use name::{Name, NameFormat, WritableName};
+pub use resolution::{RecursiveResolution, DEFAULT_RESOLVE_DEPTH};
+pub use revision::{Revision, DEFAULT_TTL};
+pub use signer::Signer;
+pub use succession::{Succession, SuccessionChain};
+pub use value::Value;
+pub use vanity::{VanityCanceller, VanityProgress, VanitySearch};
+pub use zeroize::Zeroizing;
@@ -0,0 +1,203 @@
+use std::num::NonZeroUsize;
+
+use error_stack::{report, Result, ResultExt};
+use nonzero_ext::nonzero;
+
+use crate::{
+  error::{InvalidValue, ResolutionCycle, ResolutionDepthExceeded, ResolutionError},
+  Name, Revision, Value,
+};
+
+/// The default maximum number of names that [W3NameClient::resolve_recursive](crate::W3NameClient::resolve_recursive)
+/// resolves in one call, which is the same limit that kubo uses.
+pub const DEFAULT_RESOLVE_DEPTH: NonZeroUsize = nonzero!(32usize);
+
+/// The result of resolving a [Name] recursively, following values that point at other names.
+///
+/// This keeps the verified [Revision] of every name along the way, starting with the name that was resolved,
+/// and the final value, which doesn't point at another name.
+///
+/// If a value has a path after the name, like `/ipns/<name>/docs`, the path is appended to the value of that
+/// name. So if `<name>` resolves to `/ipfs/<cid>`, the final value is `/ipfs/<cid>/docs`.
+/// See [W3NameClient::resolve_recursive](crate::W3NameClient::resolve_recursive).
+#[derive(Debug, PartialEq, Eq)]
+pub struct RecursiveResolution {
+  revisions: Vec<Revision>,
+  max_depth: usize,
+  // the paths after /ipns/<name> pointers, which are appended to the final value
+  remainder: Option<String>,
+  value: Vec<u8>,
+}
+
+impl RecursiveResolution {
+  pub(crate) fn new(max_depth: usize) -> RecursiveResolution {
+    RecursiveResolution {
+      revisions: vec![],
+      max_depth,
+      remainder: None,
+      value: vec![],
+    }
+  }
+
+  /// Adds the revision of the next name, and returns the name that its value points at, if any.
+  pub(crate) fn push(&mut self, revision: Revision) -> Result<Option<Name>, ResolutionError> {
+    let next = match revision.parsed_value() {
+      Ok(Value::Ipns { name, path }) => {
+        self.remainder = join_paths(path, self.remainder.take());
+        Some(name)
+      }
+      // a malformed pointer shouldn't be mistaken for the final value
+      Err(err) if revision.value().starts_with(b"/ipns/") => {
+        return Err(err.change_context(ResolutionError))
+      }
+      _ => None,
+    };
+
+    match &next {
+      Some(name) => {
+        if name == revision.name() || self.names().any(|n| n == name) {
+          return Err(
+            report!(ResolutionCycle {
+              name: name.to_string()
+            })
+            .change_context(ResolutionError),
+          );
+        }
+        if self.revisions.len() + 1 >= self.max_depth {
+          return Err(
+            report!(ResolutionDepthExceeded {
+              limit: self.max_depth
+            })
+            .change_context(ResolutionError),
+          );
+        }
+      }
+      None => self.value = self.final_value(&revision)?,
+    }
+
+    self.revisions.push(revision);
+    Ok(next)
+  }
+
+  fn final_value(&self, revision: &Revision) -> Result<Vec<u8>, ResolutionError> {
+    let remainder = match &self.remainder {
+      Some(remainder) => remainder,
+      None => return Ok(revision.value().to_vec()),
+    };
+    // the remaining path can only be appended to a content path
+    let value = revision
+      .parsed_value()
+      .change_context(ResolutionError)
+      .attach_printable_lazy(|| format!("can't append /{remainder} to the value"))?;
+    let path = join_paths(value.path().map(str::to_string), Some(remainder.clone()));
+    let value = match value {
+      Value::Ipfs { cid, .. } => Value::Ipfs { cid, path },
+      Value::Ipns { name, .. } => Value::Ipns { name, path },
+    };
+    Ok(value.to_string().into_bytes())
+  }
+
+  /// Returns the verified revision of each name that was resolved, starting with the name passed to
+  /// [resolve_recursive](crate::W3NameClient::resolve_recursive).
+  pub fn revisions(&self) -> &[Revision] {
+    &self.revisions
+  }
+
+  /// Returns the names that were resolved, in order.
+  pub fn names(&self) -> impl Iterator<Item = &Name> {
+    self.revisions.iter().map(Revision::name)
+  }
+
+  /// Returns the final value, which doesn't point at another name.
+  pub fn value(&self) -> &[u8] {
+    &self.value
+  }
+
+  /// Parses the final value as a content path, like [Revision::parsed_value].
+  pub fn parsed_value(&self) -> Result<Value, InvalidValue> {
+    let s = std::str::from_utf8(&self.value)
+      .map_err(|_| report!(InvalidValue).attach_printable("value is not UTF-8"))?;
+    Value::parse(s)
+  }
+}
+
+fn join_paths(first: Option<String>, second: Option<String>) -> Option<String> {
+  match (first, second) {
+    (Some(first), Some(second)) => Some(format!("{first}/{second}")),
+    (first, second) => first.or(second),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::WritableName;
+
+  const CID: &str = "bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi";
+
+  fn resolve(
+    revisions: Vec<Revision>,
+    max_depth: usize,
+  ) -> Result<RecursiveResolution, ResolutionError> {
+    let mut resolution = RecursiveResolution::new(max_depth);
+    for revision in revisions {
+      resolution.push(revision)?;
+    }
+    Ok(resolution)
+  }
+
+  #[test]
+  fn follows_pointers_and_appends_paths() {
+    let names: Vec<_> = (0..3).map(|_| WritableName::new().to_name()).collect();
+    let revisions = vec![
+      Revision::v0(&names[0], format!("/ipns/{}/docs", names[1])),
+      Revision::v0(&names[1], format!("/ipns/{}/v2", names[2])),
+      Revision::v0(&names[2], format!("/ipfs/{CID}/site")),
+    ];
+
+    let resolution = resolve(revisions, DEFAULT_RESOLVE_DEPTH.get()).unwrap();
+    assert_eq!(resolution.names().cloned().collect::<Vec<_>>(), names);
+    assert_eq!(
+      resolution.parsed_value().unwrap().to_string(),
+      format!("/ipfs/{CID}/site/v2/docs")
+    );
+  }
+
+  #[test]
+  fn keeps_values_that_are_not_paths() {
+    let resolution = resolve(
+      vec![Revision::v0(&WritableName::new().to_name(), [0xff])],
+      1,
+    )
+    .unwrap();
+    assert_eq!(resolution.value(), [0xff]);
+  }
+
+  #[test]
+  fn detects_cycles_and_runaway_depth() {
+    let a = WritableName::new().to_name();
+    let b = WritableName::new().to_name();
+
+    let err = resolve(vec![Revision::v0(&a, format!("/ipns/{a}"))], 8).unwrap_err();
+    assert_eq!(
+      err.downcast_ref::<ResolutionCycle>().unwrap().name,
+      a.to_string()
+    );
+
+    let err = resolve(
+      vec![
+        Revision::v0(&a, format!("/ipns/{b}")),
+        Revision::v0(&b, format!("/ipns/{a}/x")),
+      ],
+      8,
+    )
+    .unwrap_err();
+    assert!(err.downcast_ref::<ResolutionCycle>().is_some());
+
+    let err = resolve(vec![Revision::v0(&a, format!("/ipns/{b}"))], 1).unwrap_err();
+    assert_eq!(
+      err.downcast_ref::<ResolutionDepthExceeded>().unwrap().limit,
+      1
+    );
+  }
+}
@@ -1,6 +1,20 @@
-use crate::{error::CborError, name::Name};
+use crate::{
+  clock::{Clock, SystemClock},
+  error::{CborError, InvalidExtensionField, InvalidSuccession, InvalidUtf8, InvalidValue},
+  ipns::{check_extension_field, is_reserved_field},
+  name::Name,
+  succession::{Succession, SuccessionCbor, SUCCESSION_FIELD},
+  value::Value,
+};
 use chrono::{DateTime, Duration, SecondsFormat, Utc};
-use error_stack::{IntoReport, Result, ResultExt};
+use error_stack::{report, IntoReport, Result, ResultExt};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_bytes::ByteBuf;
+use serde_cbor::Value as CborValue;
+use std::{cmp::Ordering, collections::BTreeMap, str::from_utf8, time::Duration as StdDuration};
+
+/// The default TTL for new `Revision`s (1 hour), matching the default used by other IPNS implementations.
+pub const DEFAULT_TTL: StdDuration = StdDuration::from_secs(60 * 60);
 
 /// A `Revision` represents a single value for a name record.
 ///
@@ -11,35 +25,53 @@ use error_stack::{IntoReport, Result, ResultExt};
 /// updates to an existing `Revision`. To create the initial `Revision` (with sequence number == 0),
 /// use [Revision::v0]. Subsequent `Revision`s are created by calling [increment](Revision::increment)
 /// on an existing `Revision`.
+///
+/// A `Revision`'s value is an arbitrary byte string. Most records hold a UTF-8 content path such as
+/// `/ipfs/<cid>`, which can be read with [value_str](Revision::value_str), but IPNS allows any bytes,
+/// so the raw value is always available from [value](Revision::value).
+///
+/// Besides its validity period, each `Revision` has a TTL, which tells resolvers how long they may
+/// cache the record before checking for a newer one. The TTL defaults to [DEFAULT_TTL] and is
+/// independent of the validity period.
+///
+/// A `Revision` can also carry extension fields, which are stored next to the standard fields in the
+/// CBOR `data` map of the signed record and are covered by its v2 signature.
+/// See [set_extension](Revision::set_extension).
 #[derive(Debug, Eq, PartialEq)]
 pub struct Revision {
   name: Name,
-  value: String,
+  value: Vec<u8>,
   sequence: u64,
   validity: DateTime<Utc>,
+  ttl: StdDuration,
+  extensions: BTreeMap<String, CborValue>,
 }
 
 impl Revision {
   /// Creates a new `Revision`, specifying all fields.
   ///
   /// Note that this is crate-only; users should use [Self::v0] or [Self:::increment]
-  pub(crate) fn new<S: AsRef<str>>(
+  pub(crate) fn new<V: AsRef<[u8]>>(
     name: &Name,
-    value: S,
+    value: V,
     validity: DateTime<Utc>,
     sequence: u64,
+    ttl: StdDuration,
   ) -> Revision {
-    let value = value.as_ref().to_string();
+    let value = value.as_ref().to_vec();
     let name = name.clone();
     Revision {
       name,
       value,
       sequence,
       validity,
+      ttl,
+      extensions: BTreeMap::new(),
     }
   }
 
-  /// Creates the initial `Revision` for the given [Name], with a sequence number of 0 and the default validity period (1 year).
+  /// Creates the initial `Revision` for the given [Name], with a sequence number of 0, the default validity period (1 year)
+  /// and the [default TTL](DEFAULT_TTL).
   ///
   /// ## Example
   /// ```rust
@@ -50,19 +82,22 @@ impl Revision {
   /// let rev = Revision::v0(&name, "an initial value");
   ///
   /// assert_eq!(&name, rev.name());
-  /// assert_eq!(rev.value(), "an initial value");
+  /// assert_eq!(rev.value(), b"an initial value");
   /// # Ok(())
   /// # }
   ///
   ///
   /// ```
-  pub fn v0<S: AsRef<str>>(name: &Name, value: S) -> Revision {
-    Revision {
-      name: name.clone(),
-      value: value.as_ref().to_string(),
-      sequence: 0,
-      validity: default_validity(),
-    }
+  pub fn v0<V: AsRef<[u8]>>(name: &Name, value: V) -> Revision {
+    Revision::v0_with_clock(name, value, &SystemClock)
+  }
+
+  /// Creates the initial `Revision` for the given [Name], with the default validity period (1 year) counted
+  /// from the current time of the given [Clock].
+  ///
+  /// With a [FixedClock](crate::FixedClock), the resulting `Revision` (and any record signed from it) is reproducible.
+  pub fn v0_with_clock<V: AsRef<[u8]>>(name: &Name, value: V, clock: &dyn Clock) -> Revision {
+    Revision::new(name, value, default_validity(clock), 0, DEFAULT_TTL)
   }
 
   /// Creates the initial `Revision` for the given [Name], with an explicit validity period.
@@ -83,21 +118,80 @@ impl Revision {
   /// let rev = Revision::v0_with_validity(&name, "an initial value", expiration_date);
   ///
   /// assert_eq!(&name, rev.name());
-  /// assert_eq!(rev.value(), "an initial value");
+  /// assert_eq!(rev.value(), b"an initial value");
   /// assert_eq!(rev.validity(), &expiration_date);
   /// # Ok(())
   /// # }
   /// ```
-  pub fn v0_with_validity<S: AsRef<str>>(
+  pub fn v0_with_validity<V: AsRef<[u8]>>(
     name: &Name,
-    value: S,
+    value: V,
     validity: DateTime<Utc>,
   ) -> Revision {
-    Revision::new(name, value, validity, 0)
+    Revision::new(name, value, validity, 0, DEFAULT_TTL)
+  }
+
+  /// Creates the initial `Revision` for the given [Name], with the default validity period (1 year) and an explicit TTL.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// # fn main () -> error_stack::Result<(), w3name::error::NameError> {
+  /// use w3name::{Name, Revision};
+  /// use std::time::Duration;
+  ///
+  /// let name = Name::parse("k51qzi5uqu5dka3tmn6ipgsrq1u2bkuowdwlqcw0vibledypt1y9y5i8v8xwvu")?;
+  /// let rev = Revision::v0_with_ttl(&name, "an initial value", Duration::from_secs(300));
+  ///
+  /// assert_eq!(rev.ttl(), Duration::from_secs(300));
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn v0_with_ttl<V: AsRef<[u8]>>(name: &Name, value: V, ttl: StdDuration) -> Revision {
+    Revision::v0_with_ttl_and_clock(name, value, ttl, &SystemClock)
+  }
+
+  /// Like [Self::v0_with_ttl], but counts the default validity period from the current time of the given [Clock].
+  pub fn v0_with_ttl_and_clock<V: AsRef<[u8]>>(
+    name: &Name,
+    value: V,
+    ttl: StdDuration,
+    clock: &dyn Clock,
+  ) -> Revision {
+    Revision::new(name, value, default_validity(clock), 0, ttl)
+  }
+
+  /// Creates the initial `Revision` for the given [Name] from a validated content path [Value].
+  ///
+  /// This is the same as [Self::v0], but rules out publishing a malformed `/ipfs/` or `/ipns/` path.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// # fn main() -> error_stack::Result<(), w3name::error::InvalidValue> {
+  /// use w3name::{Name, Revision, Value};
+  ///
+  /// let name = Name::parse("k51qzi5uqu5dka3tmn6ipgsrq1u2bkuowdwlqcw0vibledypt1y9y5i8v8xwvu").unwrap();
+  /// let value = Value::parse("/ipfs/bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi")?;
+  /// let rev = Revision::v0_value(&name, &value);
+  ///
+  /// assert_eq!(rev.parsed_value()?, value);
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn v0_value(name: &Name, value: &Value) -> Revision {
+    Revision::v0_value_with_clock(name, value, &SystemClock)
+  }
+
+  /// Like [Self::v0_value], but counts the default validity period from the current time of the given [Clock].
+  pub fn v0_value_with_clock(name: &Name, value: &Value, clock: &dyn Clock) -> Revision {
+    Revision::v0_with_clock(name, value.to_string(), clock)
   }
 
   /// Creates a new `Revision` with the given `value` and an incremented sequence number, using the default validity period (1 year).
   ///
+  /// The new `Revision` keeps the TTL and extension fields of this one.
+  ///
   /// ## Example
   ///
   /// ```rust
@@ -112,31 +206,113 @@ impl Revision {
   /// assert_eq!(&name, rev2.name());
   /// assert_eq!(rev.sequence(), 0);
   /// assert_eq!(rev2.sequence(), 1);
-  /// assert_eq!(rev.value(), "an initial value");
-  /// assert_eq!(rev2.value(), "a new value");
+  /// assert_eq!(rev.value(), b"an initial value");
+  /// assert_eq!(rev2.value(), b"a new value");
   ///
   /// # Ok(())
   /// # }
   /// ```
-  pub fn increment<S: AsRef<str>>(&self, value: S) -> Revision {
-    Self::increment_with_validity(&self, value, default_validity())
+  pub fn increment<V: AsRef<[u8]>>(&self, value: V) -> Revision {
+    self.increment_with_clock(value, &SystemClock)
+  }
+
+  /// Creates a new `Revision` with the given `value` and an incremented sequence number, using the default
+  /// validity period (1 year) counted from the current time of the given [Clock].
+  ///
+  /// The new `Revision` keeps the TTL and extension fields of this one.
+  pub fn increment_with_clock<V: AsRef<[u8]>>(&self, value: V, clock: &dyn Clock) -> Revision {
+    self.increment_with_validity(value, default_validity(clock))
   }
 
   /// Creates a new `Revision` with the given `value` and an incremented sequence number, with an explicit validity period.
   ///
-  /// Note that `validity` is an end-of-life timestamp, not a duration.
-  pub fn increment_with_validity<S: AsRef<str>>(
+  /// Note that `validity` is an end-of-life timestamp, not a duration. The new `Revision` keeps the TTL
+  /// and extension fields of this one.
+  pub fn increment_with_validity<V: AsRef<[u8]>>(
     &self,
-    value: S,
+    value: V,
     validity: DateTime<Utc>,
   ) -> Revision {
-    let sequence = self.sequence + 1;
-    Revision {
-      name: self.name.clone(),
-      value: value.as_ref().to_string(),
-      sequence,
-      validity,
-    }
+    let mut rev = Revision::new(&self.name, value, validity, self.sequence + 1, self.ttl);
+    rev.extensions = self.extensions.clone();
+    rev
+  }
+
+  /// Creates a new `Revision` with the given `value` and an incremented sequence number, using the
+  /// default validity period (1 year) and an explicit TTL.
+  pub fn increment_with_ttl<V: AsRef<[u8]>>(&self, value: V, ttl: StdDuration) -> Revision {
+    self.increment_with_ttl_and_clock(value, ttl, &SystemClock)
+  }
+
+  /// Like [Self::increment_with_ttl], but counts the default validity period from the current time of the given [Clock].
+  pub fn increment_with_ttl_and_clock<V: AsRef<[u8]>>(
+    &self,
+    value: V,
+    ttl: StdDuration,
+    clock: &dyn Clock,
+  ) -> Revision {
+    let mut rev = Revision::new(
+      &self.name,
+      value,
+      default_validity(clock),
+      self.sequence + 1,
+      ttl,
+    );
+    rev.extensions = self.extensions.clone();
+    rev
+  }
+
+  /// Creates a new `Revision` from a validated content path [Value], with an incremented sequence number.
+  ///
+  /// This is the same as [Self::increment], but rules out publishing a malformed `/ipfs/` or `/ipns/` path.
+  pub fn increment_value(&self, value: &Value) -> Revision {
+    self.increment_value_with_clock(value, &SystemClock)
+  }
+
+  /// Like [Self::increment_value], but counts the default validity period from the current time of the given [Clock].
+  pub fn increment_value_with_clock(&self, value: &Value, clock: &dyn Clock) -> Revision {
+    self.increment_with_clock(value.to_string(), clock)
+  }
+
+  /// Creates the first `Revision` for a name that is retired straight away in favor of the successor in `succession`.
+  ///
+  /// See [increment_succession](Self::increment_succession).
+  pub fn v0_succession(name: &Name, succession: &Succession) -> Revision {
+    Revision::v0_succession_with_clock(name, succession, &SystemClock)
+  }
+
+  /// Like [Self::v0_succession], but counts the default validity period from the current time of the given [Clock].
+  pub fn v0_succession_with_clock(
+    name: &Name,
+    succession: &Succession,
+    clock: &dyn Clock,
+  ) -> Revision {
+    let mut rev = Revision::v0_value_with_clock(name, &Value::ipns(succession.successor()), clock);
+    rev.set_succession(succession);
+    rev
+  }
+
+  /// Creates a new `Revision` that retires this name in favor of the successor in `succession`.
+  ///
+  /// The value is set to `/ipns/<successor>`, and the signed [Succession] statement is stored in an extension field.
+  /// The validity period is the default of 1 year, so republish the record before it expires if followers may
+  /// still need to find the successor.
+  ///
+  /// To undo a rotation, publish a later revision with any other value.
+  pub fn increment_succession(&self, succession: &Succession) -> Revision {
+    self.increment_succession_with_clock(succession, &SystemClock)
+  }
+
+  /// Like [Self::increment_succession], but counts the default validity period from the current time of the
+  /// given [Clock].
+  pub fn increment_succession_with_clock(
+    &self,
+    succession: &Succession,
+    clock: &dyn Clock,
+  ) -> Revision {
+    let mut rev = self.increment_value_with_clock(&Value::ipns(succession.successor()), clock);
+    rev.set_succession(succession);
+    rev
   }
 
   /// Returns a reference to this `Revision`'s [Name].
@@ -144,11 +320,68 @@ impl Revision {
     &self.name
   }
 
-  /// Returns a reference to this `Revision`'s value.
-  pub fn value(&self) -> &str {
+  /// Returns a reference to this `Revision`'s raw value bytes.
+  pub fn value(&self) -> &[u8] {
     &self.value
   }
 
+  /// Returns this `Revision`'s value as a string slice, or an [InvalidUtf8] error if the value isn't valid UTF-8.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// # fn main() -> error_stack::Result<(), w3name::error::InvalidUtf8> {
+  /// use w3name::{Name, Revision};
+  ///
+  /// let name = Name::parse("k51qzi5uqu5dka3tmn6ipgsrq1u2bkuowdwlqcw0vibledypt1y9y5i8v8xwvu").unwrap();
+  /// let rev = Revision::v0(&name, "an initial value");
+  /// assert_eq!(rev.value_str()?, "an initial value");
+  ///
+  /// let binary = rev.increment([0xff, 0xfe]);
+  /// assert!(binary.value_str().is_err());
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn value_str(&self) -> Result<&str, InvalidUtf8> {
+    from_utf8(&self.value).report().change_context(InvalidUtf8)
+  }
+
+  /// Parses this `Revision`'s value as an `/ipfs/` or `/ipns/` content path.
+  ///
+  /// Returns an [InvalidValue] error if the value is not a valid content path.
+  pub fn parsed_value(&self) -> Result<Value, InvalidValue> {
+    let s = self.value_str().change_context(InvalidValue)?;
+    Value::parse(s)
+  }
+
+  /// Converts this `Revision`'s value into any type that can be built from a byte slice, for example a binary [Cid](cid::Cid).
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// # fn main() -> error_stack::Result<(), w3name::error::InvalidValue> {
+  /// use w3name::{Name, Revision};
+  /// use cid::Cid;
+  ///
+  /// let name = Name::parse("k51qzi5uqu5dka3tmn6ipgsrq1u2bkuowdwlqcw0vibledypt1y9y5i8v8xwvu").unwrap();
+  /// let cid = Cid::try_from("bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi").unwrap();
+  /// let rev = Revision::v0(&name, cid.to_bytes());
+  ///
+  /// let value: Cid = rev.value_as()?;
+  /// assert_eq!(value, cid);
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn value_as<'a, T>(&'a self) -> Result<T, InvalidValue>
+  where
+    T: TryFrom<&'a [u8]>,
+    T::Error: std::error::Error + Send + Sync + 'static,
+  {
+    T::try_from(&self.value[..])
+      .report()
+      .change_context(InvalidValue)
+  }
+
   /// Returns this `Revision`'s sequence number.
   pub fn sequence(&self) -> u64 {
     self.sequence
@@ -159,6 +392,145 @@ impl Revision {
     &self.validity
   }
 
+  /// Returns this `Revision`'s TTL, i.e. how long resolvers may cache the record.
+  pub fn ttl(&self) -> StdDuration {
+    self.ttl
+  }
+
+  /// Compares two `Revision`s of the same name by how authoritative they are, following the IPNS ordering rules.
+  ///
+  /// The `Revision` with the higher sequence number wins. If the sequence numbers are equal, the one with the
+  /// later validity wins. Any remaining ties are broken deterministically by comparing the value, TTL and extension
+  /// fields, so every caller picks the same `Revision`. `Ordering::Greater` means that `self` should be preferred.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use std::cmp::Ordering;
+  /// use w3name::{Revision, WritableName};
+  ///
+  /// let name = WritableName::new().to_name();
+  /// let rev0 = Revision::v0(&name, "first");
+  /// let rev1 = rev0.increment("second");
+  ///
+  /// assert_eq!(rev1.compare(&rev0), Ordering::Greater);
+  /// ```
+  pub fn compare(&self, other: &Revision) -> Ordering {
+    self
+      .sequence
+      .cmp(&other.sequence)
+      .then_with(|| self.validity.cmp(&other.validity))
+      .then_with(|| self.value.cmp(&other.value))
+      .then_with(|| self.ttl.cmp(&other.ttl))
+      .then_with(|| self.extensions.cmp(&other.extensions))
+      .then_with(|| self.name.to_bytes().cmp(&other.name.to_bytes()))
+  }
+
+  /// Returns the most authoritative of the given `Revision`s according to [compare](Self::compare),
+  /// or `None` if there are none.
+  pub fn best<'a, I: IntoIterator<Item = &'a Revision>>(revisions: I) -> Option<&'a Revision> {
+    revisions.into_iter().max_by(|a, b| a.compare(b))
+  }
+
+  /// Sets an extension field, which will be included in the signed `data` map of the IPNS record.
+  ///
+  /// The value can be any type that serializes to CBOR without floats. Keys used by the standard
+  /// IPNS fields (`Value`, `Validity`, `ValidityType`, `Sequence` and `TTL`) can't be used.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// # fn main() -> error_stack::Result<(), w3name::error::InvalidExtensionField> {
+  /// use w3name::{Name, Revision};
+  ///
+  /// let name = Name::parse("k51qzi5uqu5dka3tmn6ipgsrq1u2bkuowdwlqcw0vibledypt1y9y5i8v8xwvu").unwrap();
+  /// let mut rev = Revision::v0(&name, "an initial value");
+  /// rev.set_extension("build", &42u32)?;
+  ///
+  /// assert_eq!(rev.extension::<u32>("build")?, Some(42));
+  /// assert!(rev.set_extension("Sequence", &7u64).is_err());
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn set_extension<T: Serialize>(
+    &mut self,
+    key: &str,
+    value: &T,
+  ) -> Result<(), InvalidExtensionField> {
+    if is_reserved_field(key) {
+      return Err(
+        report!(InvalidExtensionField).attach_printable(format!("{key} is a reserved field")),
+      );
+    }
+    let value = serde_cbor::value::to_value(value)
+      .report()
+      .change_context(InvalidExtensionField)?;
+    check_extension_field(&value).change_context(InvalidExtensionField)?;
+    self.extensions.insert(key.to_string(), value);
+    Ok(())
+  }
+
+  /// Returns the extension field with the given key, deserialized as `T`, or `None` if there is no such field.
+  pub fn extension<T: DeserializeOwned>(
+    &self,
+    key: &str,
+  ) -> Result<Option<T>, InvalidExtensionField> {
+    extension_from_map(&self.extensions, key)
+  }
+
+  /// Removes the extension field with the given key, returning true if it was present.
+  pub fn remove_extension(&mut self, key: &str) -> bool {
+    self.extensions.remove(key).is_some()
+  }
+
+  /// Returns an iterator over the keys of this `Revision`'s extension fields.
+  pub fn extension_keys(&self) -> impl Iterator<Item = &str> {
+    self.extensions.keys().map(String::as_str)
+  }
+
+  /// Returns the [Succession] statement stored in this `Revision`, if any. The statement is not verified.
+  pub fn succession(&self) -> Result<Option<Succession>, InvalidExtensionField> {
+    let cbor: Option<SuccessionCbor> = self.extension(SUCCESSION_FIELD)?;
+    cbor
+      .map(Succession::from_cbor)
+      .transpose()
+      .change_context(InvalidExtensionField)
+  }
+
+  /// Returns the name that succeeds this `Revision`'s name, if this is a valid succession record.
+  ///
+  /// A valid succession record has a [Succession] statement signed by this name's key, and its value
+  /// is `/ipns/<successor>`. Records whose value points anywhere else don't have a successor, even if they
+  /// kept the statement from an earlier revision.
+  pub fn successor(&self) -> Result<Option<Name>, InvalidSuccession> {
+    let succession = match self.succession().change_context(InvalidSuccession)? {
+      Some(succession) => succession,
+      None => return Ok(None),
+    };
+    match self.parsed_value() {
+      Ok(Value::Ipns { name, path: None }) if &name == succession.successor() => {}
+      _ => return Ok(None),
+    }
+
+    succession.verify(&self.name)?;
+    Ok(Some(succession.successor().clone()))
+  }
+
+  fn set_succession(&mut self, succession: &Succession) {
+    let value = serde_cbor::value::to_value(succession.to_cbor())
+      .expect("succession statements can be encoded as CBOR");
+    self.extensions.insert(SUCCESSION_FIELD.to_string(), value);
+  }
+
+  pub(crate) fn extensions(&self) -> &BTreeMap<String, CborValue> {
+    &self.extensions
+  }
+
+  pub(crate) fn with_extensions(mut self, extensions: BTreeMap<String, CborValue>) -> Revision {
+    self.extensions = extensions;
+    self
+  }
+
   /// Returns this `Revision`'s validity period as a String, suitable for inclusion in an IPNS record.
   pub fn validity_string(&self) -> String {
     self.validity.to_rfc3339_opts(SecondsFormat::Nanos, true)
@@ -188,9 +560,11 @@ impl Revision {
   pub fn encode(&self) -> Result<Vec<u8>, CborError> {
     let data = RevisionCbor {
       name: self.name.to_string(),
-      value: self.value.clone(),
+      value: RevisionValueCbor::from_bytes(&self.value),
       sequence: self.sequence,
       validity: self.validity_string(),
+      ttl: Some(ttl_nanos(self.ttl)),
+      extensions: self.extensions.clone(),
     };
     let bytes = serde_cbor::to_vec(&data)
       .report()
@@ -225,27 +599,82 @@ impl Revision {
       .report()
       .change_context(CborError)?;
 
+    let ttl = data.ttl.map(StdDuration::from_nanos).unwrap_or(DEFAULT_TTL);
+
     let rev = Revision {
       name,
-      value: data.value,
+      value: data.value.into_bytes(),
       sequence: data.sequence,
       validity: validity.into(),
+      ttl,
+      extensions: data.extensions,
     };
 
     Ok(rev)
   }
 }
 
-fn default_validity() -> DateTime<Utc> {
-  Utc::now().checked_add_signed(Duration::weeks(52)).unwrap()
+fn default_validity(clock: &dyn Clock) -> DateTime<Utc> {
+  clock.now().checked_add_signed(Duration::weeks(52)).unwrap()
+}
+
+/// Reads a typed extension field from a map of extension fields.
+pub(crate) fn extension_from_map<T: DeserializeOwned>(
+  extensions: &BTreeMap<String, CborValue>,
+  key: &str,
+) -> Result<Option<T>, InvalidExtensionField> {
+  match extensions.get(key) {
+    Some(value) => serde_cbor::value::from_value(value.clone())
+      .report()
+      .change_context(InvalidExtensionField)
+      .map(Some),
+    None => Ok(None),
+  }
+}
+
+/// Returns a TTL as a number of nanoseconds, as used in IPNS records, saturating at `u64::MAX`.
+pub(crate) fn ttl_nanos(ttl: StdDuration) -> u64 {
+  u64::try_from(ttl.as_nanos()).unwrap_or(u64::MAX)
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 struct RevisionCbor {
   name: String,
-  value: String,
+  value: RevisionValueCbor,
   sequence: u64,
   validity: String,
+  // added after the initial release; revisions encoded by older versions use the default TTL
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  ttl: Option<u64>,
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  extensions: BTreeMap<String, CborValue>,
+}
+
+/// The encoded form of a `Revision`'s value.
+///
+/// Older versions of this crate could only encode string values, so UTF-8 values are still written as CBOR
+/// text strings, which keeps them readable by those versions. Other values are written as CBOR byte strings.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+enum RevisionValueCbor {
+  Text(String),
+  Bytes(ByteBuf),
+}
+
+impl RevisionValueCbor {
+  fn from_bytes(value: &[u8]) -> RevisionValueCbor {
+    match from_utf8(value) {
+      Ok(s) => RevisionValueCbor::Text(s.to_string()),
+      Err(_) => RevisionValueCbor::Bytes(ByteBuf::from(value.to_vec())),
+    }
+  }
+
+  fn into_bytes(self) -> Vec<u8> {
+    match self {
+      RevisionValueCbor::Text(s) => s.into_bytes(),
+      RevisionValueCbor::Bytes(b) => b.into_vec(),
+    }
+  }
 }
 
 #[cfg(test)]
@@ -268,4 +697,126 @@ mod tests {
 
     assert_eq!(rev, rev2);
   }
+
+  #[test]
+  fn ttl_roundtrip() {
+    let w = WritableName::new();
+    let rev = Revision::v0_with_ttl(&w.to_name(), "a value", StdDuration::from_secs(30));
+    let rev2 = Revision::decode(&rev.encode().unwrap()).unwrap();
+    assert_eq!(rev2.ttl(), StdDuration::from_secs(30));
+
+    // incrementing keeps the TTL unless a new one is given
+    assert_eq!(rev.increment("next").ttl(), StdDuration::from_secs(30));
+    assert_eq!(
+      rev
+        .increment_with_ttl("next", StdDuration::from_secs(5))
+        .ttl(),
+      StdDuration::from_secs(5)
+    );
+  }
+
+  #[test]
+  fn decode_without_ttl_uses_default() {
+    let w = WritableName::new();
+    let legacy = serde_cbor::to_vec(&LegacyRevisionCbor {
+      name: w.to_string(),
+      value: "a value".to_string(),
+      sequence: 3,
+      validity: "2023-09-23T00:00:00Z".to_string(),
+    })
+    .unwrap();
+
+    let rev = Revision::decode(&legacy).unwrap();
+    assert_eq!(rev.sequence(), 3);
+    assert_eq!(rev.ttl(), DEFAULT_TTL);
+  }
+
+  #[test]
+  fn binary_value_roundtrip() {
+    let w = WritableName::new();
+    let rev = Revision::v0(&w.to_name(), [0x01, 0x70, 0xff, 0x00]);
+    assert!(rev.value_str().is_err());
+
+    let rev2 = Revision::decode(&rev.encode().unwrap()).unwrap();
+    assert_eq!(rev, rev2);
+    assert_eq!(rev2.value(), &[0x01, 0x70, 0xff, 0x00]);
+  }
+
+  #[test]
+  fn text_value_is_encoded_as_cbor_text() {
+    let rev = make_test_revision("it's a test");
+    let legacy: LegacyRevisionCbor = serde_cbor::from_slice(&rev.encode().unwrap()).unwrap();
+    assert_eq!(legacy.value, "it's a test");
+  }
+
+  #[test]
+  fn extensions_roundtrip() {
+    let mut rev = make_test_revision("it's a test");
+    rev.set_extension("build", &"abc123").unwrap();
+    rev.set_extension("tags", &vec!["a", "b"]).unwrap();
+
+    let rev2 = Revision::decode(&rev.encode().unwrap()).unwrap();
+    assert_eq!(rev, rev2);
+    assert_eq!(
+      rev2.extension::<String>("build").unwrap(),
+      Some("abc123".to_string())
+    );
+    assert_eq!(rev2.extension::<String>("missing").unwrap(), None);
+    assert!(rev2.extension::<u64>("build").is_err());
+
+    let rev3 = rev2.increment("next");
+    assert_eq!(
+      rev3.extension_keys().collect::<Vec<_>>(),
+      vec!["build", "tags"]
+    );
+  }
+
+  #[test]
+  fn rejects_invalid_extensions() {
+    let mut rev = make_test_revision("it's a test");
+    assert!(rev.set_extension("TTL", &1u64).is_err());
+    assert!(rev.set_extension("ratio", &0.5f64).is_err());
+  }
+
+  #[test]
+  fn validity_from_clock() {
+    let name = WritableName::new().to_name();
+    let now = Utc::now() - Duration::days(400);
+    let clock = crate::FixedClock::new(now);
+
+    let rev = Revision::v0_with_clock(&name, "a value", &clock);
+    assert_eq!(rev.validity(), &(now + Duration::weeks(52)));
+    assert_eq!(rev, Revision::v0_with_clock(&name, "a value", &clock));
+
+    let rev2 = rev.increment_with_clock("another value", &clock);
+    assert_eq!(rev2.validity(), rev.validity());
+    assert_eq!(rev2.sequence(), 1);
+  }
+
+  #[test]
+  fn compare_revisions() {
+    let name = WritableName::new().to_name();
+    let validity = Utc::now();
+    let rev0 = Revision::v0_with_validity(&name, "b", validity);
+    let later = Revision::v0_with_validity(&name, "a", validity + Duration::days(1));
+    let rev1 = rev0.increment_with_validity("a", validity - Duration::days(1));
+
+    // sequence beats validity, and validity beats value
+    assert_eq!(rev1.compare(&later), Ordering::Greater);
+    assert_eq!(later.compare(&rev0), Ordering::Greater);
+    assert_eq!(Revision::best([&rev0, &later, &rev1]), Some(&rev1));
+
+    let tie = Revision::v0_with_validity(&name, "a", validity);
+    assert_eq!(rev0.compare(&tie), Ordering::Greater);
+    assert_eq!(tie.compare(&rev0), Ordering::Less);
+    assert_eq!(rev0.compare(&rev0), Ordering::Equal);
+  }
+
+  #[derive(serde::Serialize, serde::Deserialize)]
+  struct LegacyRevisionCbor {
+    name: String,
+    value: String,
+    sequence: u64,
+    validity: String,
+  }
 }
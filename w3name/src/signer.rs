@@ -0,0 +1,141 @@
+use async_trait::async_trait;
+use error_stack::{IntoReport, Result, ResultExt};
+use libp2p_core::identity::PublicKey;
+
+use crate::{error::SigningError, Name, WritableName};
+
+/// A `Signer` signs name records on behalf of a [Name], without necessarily having the private key in memory.
+///
+/// [WritableName] is the simplest `Signer`. Implement this trait to keep keys in a hardware token,
+/// a cloud KMS or a separate process, and pass the signer to [IpnsRecord::sign](crate::IpnsRecord::sign)
+/// or [W3NameClient::publish](crate::W3NameClient::publish).
+///
+/// `sign` must return a signature in the format that libp2p uses for the key type, i.e. the one
+/// [PublicKey::verify] accepts: raw 64-byte signatures for ed25519, and DER-encoded ECDSA signatures over
+/// the SHA-256 hash of the message for secp256k1 and P-256. Records are checked against [public_key](Self::public_key)
+/// after signing, so a signer that uses the wrong key or format fails with a [SigningError].
+///
+/// ## Example
+///
+/// ```rust
+/// # #[tokio::main]
+/// # async fn main() -> error_stack::Result<(), w3name::error::IpnsError> {
+/// use libp2p_core::identity::PublicKey;
+/// use w3name::{async_trait, error::SigningError, IpnsRecord, Revision, Signer, WritableName};
+///
+/// /// A signer that logs everything it signs.
+/// struct LoggingSigner(WritableName);
+///
+/// #[async_trait]
+/// impl Signer for LoggingSigner {
+///   fn public_key(&self) -> PublicKey {
+///     self.0.public_key()
+///   }
+///
+///   async fn sign(&self, data: &[u8]) -> error_stack::Result<Vec<u8>, SigningError> {
+///     println!("signing {} bytes for {}", data.len(), self.name());
+///     self.0.sign(data).await
+///   }
+/// }
+///
+/// let signer = LoggingSigner(WritableName::new());
+/// let rev = Revision::v0(&signer.name(), "a value");
+/// let record = IpnsRecord::sign(&signer, &rev).await?;
+/// record.verify(&signer.name())?;
+/// # Ok(())
+/// # }
+/// ```
+#[async_trait]
+pub trait Signer: Send + Sync {
+  /// Returns the public key that matches the signing key.
+  fn public_key(&self) -> PublicKey;
+
+  /// Signs `data` with the private key, returning the signature bytes.
+  async fn sign(&self, data: &[u8]) -> Result<Vec<u8>, SigningError>;
+
+  /// Returns the [Name] whose records this signer can sign.
+  fn name(&self) -> Name {
+    Name::from_public_key(self.public_key())
+  }
+}
+
+#[async_trait]
+impl Signer for WritableName {
+  fn public_key(&self) -> PublicKey {
+    self.keypair().public()
+  }
+
+  async fn sign(&self, data: &[u8]) -> Result<Vec<u8>, SigningError> {
+    self
+      .keypair()
+      .sign(data)
+      .report()
+      .change_context(SigningError)
+  }
+
+  fn name(&self) -> Name {
+    self.to_name()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{IpnsRecord, Revision, SigningOptions};
+
+  /// Signs with a different key than the one it claims to have.
+  struct WrongKeySigner {
+    claimed: WritableName,
+    actual: WritableName,
+  }
+
+  #[async_trait]
+  impl Signer for WrongKeySigner {
+    fn public_key(&self) -> PublicKey {
+      self.claimed.public_key()
+    }
+
+    async fn sign(&self, data: &[u8]) -> Result<Vec<u8>, SigningError> {
+      self.actual.sign(data).await
+    }
+  }
+
+  #[tokio::test]
+  async fn signs_records_like_writable_name() {
+    for name in [
+      WritableName::new(),
+      WritableName::new_secp256k1(),
+      WritableName::new_ecdsa(),
+    ] {
+      let signer: &dyn Signer = &name;
+      assert_eq!(signer.name(), name.to_name());
+
+      let rev = Revision::v0(&name.to_name(), "value");
+      let record = IpnsRecord::sign(signer, &rev).await.unwrap();
+      record.verify(&name.to_name()).unwrap();
+
+      // ed25519 signatures are deterministic, so both paths give the same record
+      if let libp2p_core::identity::Keypair::Ed25519(_) = name.keypair() {
+        assert_eq!(record, IpnsRecord::new(&name, &rev).unwrap());
+      }
+    }
+  }
+
+  #[tokio::test]
+  async fn rejects_signatures_from_the_wrong_key() {
+    let signer = WrongKeySigner {
+      claimed: WritableName::new(),
+      actual: WritableName::new(),
+    };
+    let rev = Revision::v0(&signer.name(), "value");
+    for options in [
+      SigningOptions::default(),
+      SigningOptions::new().v2_only(true),
+    ] {
+      let err = IpnsRecord::sign_with_options(&signer, &rev, &options)
+        .await
+        .unwrap_err();
+      assert!(err.downcast_ref::<SigningError>().is_some());
+    }
+  }
+}
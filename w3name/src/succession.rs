@@ -0,0 +1,329 @@
+use error_stack::{report, IntoReport, Result, ResultExt};
+use libp2p_core::identity::PublicKey;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  error::{InvalidSuccession, SigningError},
+  Name, Revision, Signer,
+};
+
+/// The [Revision] extension field that holds a [Succession] statement.
+pub(crate) const SUCCESSION_FIELD: &str = "Successor";
+
+/// Chains longer than this are rejected, so a long chain can't keep a resolver busy.
+const MAX_CHAIN_LENGTH: usize = 16;
+
+const SIGNATURE_PREFIX: &[u8] = b"w3name-succession:";
+
+/// A `Succession` is a statement, signed by the key of a retired [Name], that another name replaces it.
+///
+/// To rotate a name's key, publish a final record for the old name whose value is `/ipns/<new name>`, and which
+/// carries a `Succession` in its extension fields. [W3NameClient::rotate](crate::W3NameClient::rotate) does this
+/// in one step; to build the record yourself, use [Revision::increment_succession].
+///
+/// Since the statement is signed by the old key, followers can trust that the new name was chosen by the owner
+/// of the old one. [W3NameClient::resolve_succession](crate::W3NameClient::resolve_succession) follows a chain of
+/// successions to the current name.
+///
+/// ## Example
+///
+/// ```rust
+/// # #[tokio::main]
+/// # async fn main() -> error_stack::Result<(), w3name::error::InvalidSuccession> {
+/// use w3name::{Revision, Succession, WritableName};
+///
+/// let old = WritableName::new();
+/// let new = WritableName::new();
+///
+/// let succession = Succession::sign(&old, &new.to_name()).await.unwrap();
+/// succession.verify(&old.to_name())?;
+///
+/// let rev = Revision::v0(&old.to_name(), "an old value").increment_succession(&succession);
+/// assert_eq!(rev.successor()?, Some(new.to_name()));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Succession {
+  successor: Name,
+  signature: Vec<u8>,
+  // included if the key isn't embedded in the predecessor's name, so that the statement can be verified on its own
+  public_key: Option<PublicKey>,
+}
+
+impl Succession {
+  /// Signs a statement that `successor` replaces the name of `signer`.
+  pub async fn sign<S: Signer + ?Sized>(
+    signer: &S,
+    successor: &Name,
+  ) -> Result<Succession, SigningError> {
+    let predecessor = signer.name();
+    if &predecessor == successor {
+      return Err(report!(SigningError).attach_printable("a name can't succeed itself"));
+    }
+
+    let signature = signer
+      .sign(&signature_message(&predecessor, successor))
+      .await?;
+    let public_key = if predecessor.embeds_public_key() {
+      None
+    } else {
+      Some(signer.public_key())
+    };
+    Ok(Succession {
+      successor: successor.clone(),
+      signature,
+      public_key,
+    })
+  }
+
+  /// Returns the name that replaces the predecessor.
+  pub fn successor(&self) -> &Name {
+    &self.successor
+  }
+
+  /// Checks that this statement was signed by the key of `predecessor`.
+  pub fn verify(&self, predecessor: &Name) -> Result<(), InvalidSuccession> {
+    if predecessor == &self.successor {
+      return Err(report!(InvalidSuccession).attach_printable("a name can't succeed itself"));
+    }
+
+    let public_key = match (predecessor.public_key(), &self.public_key) {
+      (Some(public_key), _) => public_key,
+      (None, Some(public_key)) if predecessor.matches_public_key(public_key) => public_key,
+      (None, Some(_)) => {
+        return Err(
+          report!(InvalidSuccession)
+            .attach_printable("the statement's public key doesn't belong to the predecessor"),
+        )
+      }
+      (None, None) => {
+        return Err(
+          report!(InvalidSuccession).attach_printable("no public key for the predecessor"),
+        )
+      }
+    };
+
+    let msg = signature_message(predecessor, &self.successor);
+    if public_key.verify(&msg, &self.signature) {
+      Ok(())
+    } else {
+      Err(report!(InvalidSuccession).attach_printable("invalid signature"))
+    }
+  }
+
+  pub(crate) fn to_cbor(&self) -> SuccessionCbor {
+    SuccessionCbor {
+      successor: self.successor.to_bytes(),
+      signature: self.signature.clone(),
+      public_key: self
+        .public_key
+        .as_ref()
+        .map(PublicKey::to_protobuf_encoding)
+        .unwrap_or_default(),
+    }
+  }
+
+  pub(crate) fn from_cbor(cbor: SuccessionCbor) -> Result<Succession, InvalidSuccession> {
+    let successor = Name::try_from(cbor.successor.as_slice())
+      .report()
+      .change_context(InvalidSuccession)
+      .attach_printable("invalid successor name")?;
+    let public_key = if cbor.public_key.is_empty() {
+      None
+    } else {
+      let public_key = PublicKey::from_protobuf_encoding(&cbor.public_key)
+        .report()
+        .change_context(InvalidSuccession)
+        .attach_printable("invalid public key")?;
+      Some(public_key)
+    };
+    Ok(Succession {
+      successor,
+      signature: cbor.signature,
+      public_key,
+    })
+  }
+}
+
+/// The encoding of a [Succession] in a [Revision]'s extension fields.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct SuccessionCbor {
+  #[serde(rename = "Name", with = "serde_bytes")]
+  successor: Vec<u8>,
+  #[serde(rename = "Signature", with = "serde_bytes")]
+  signature: Vec<u8>,
+  #[serde(
+    rename = "PublicKey",
+    with = "serde_bytes",
+    default,
+    skip_serializing_if = "Vec::is_empty"
+  )]
+  public_key: Vec<u8>,
+}
+
+/// The message signed by the predecessor's key, which commits to both names.
+fn signature_message(predecessor: &Name, successor: &Name) -> Vec<u8> {
+  let predecessor = predecessor.to_bytes();
+  let successor = successor.to_bytes();
+
+  let mut msg = SIGNATURE_PREFIX.to_vec();
+  // CIDs aren't self-delimiting, so each one is prefixed with its length
+  for name in [predecessor, successor] {
+    msg.extend((name.len() as u32).to_be_bytes());
+    msg.extend(name);
+  }
+  msg
+}
+
+/// The [Revision]s of a [Name] and the names that succeeded it, in order.
+///
+/// The first revision belongs to the name that was resolved, and the last one to the current name.
+/// See [W3NameClient::resolve_succession](crate::W3NameClient::resolve_succession).
+#[derive(Debug, PartialEq, Eq)]
+pub struct SuccessionChain {
+  revisions: Vec<Revision>,
+}
+
+impl SuccessionChain {
+  pub(crate) fn new() -> SuccessionChain {
+    SuccessionChain { revisions: vec![] }
+  }
+
+  /// Adds the latest revision of the next name in the chain, and returns the name that succeeds it, if any.
+  pub(crate) fn push(&mut self, revision: Revision) -> Result<Option<Name>, InvalidSuccession> {
+    let successor = revision.successor()?;
+    self.revisions.push(revision);
+
+    if let Some(next) = &successor {
+      if self.names().any(|name| name == next) {
+        return Err(
+          report!(InvalidSuccession).attach_printable(format!("succession cycle at {next}")),
+        );
+      }
+      if self.revisions.len() >= MAX_CHAIN_LENGTH {
+        return Err(report!(InvalidSuccession).attach_printable(format!(
+          "succession chain is longer than {MAX_CHAIN_LENGTH} names"
+        )));
+      }
+    }
+    Ok(successor)
+  }
+
+  /// Returns the latest revision of each name in the chain, starting with the name that was resolved.
+  pub fn revisions(&self) -> &[Revision] {
+    &self.revisions
+  }
+
+  /// Returns the names in the chain, starting with the name that was resolved.
+  pub fn names(&self) -> impl Iterator<Item = &Name> {
+    self.revisions.iter().map(Revision::name)
+  }
+
+  /// Returns the latest revision of the current name, at the end of the chain.
+  pub fn current(&self) -> &Revision {
+    self
+      .revisions
+      .last()
+      .expect("chains have at least one revision")
+  }
+
+  /// Converts this chain into the latest revision of the current name.
+  pub fn into_current(mut self) -> Revision {
+    self
+      .revisions
+      .pop()
+      .expect("chains have at least one revision")
+  }
+
+  /// Returns true if the resolved name has been succeeded by another name.
+  pub fn is_rotated(&self) -> bool {
+    self.revisions.len() > 1
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{IpnsRecord, WritableName};
+
+  #[tokio::test]
+  async fn signs_and_verifies_successions() {
+    let old = WritableName::new();
+    let new = WritableName::new_secp256k1();
+    let succession = Succession::sign(&old, &new.to_name()).await.unwrap();
+    succession.verify(&old.to_name()).unwrap();
+    assert!(succession.verify(&WritableName::new().to_name()).is_err());
+    assert!(Succession::sign(&old, &old.to_name()).await.is_err());
+
+    // the statement survives signing, encoding and decoding the record
+    let rev = Revision::v0(&old.to_name(), "old").increment_succession(&succession);
+    let record =
+      IpnsRecord::decode(&IpnsRecord::new(&old, &rev).unwrap().encode().unwrap()).unwrap();
+    let decoded = record.to_revision(&old.to_name()).unwrap();
+    assert_eq!(decoded.succession().unwrap(), Some(succession));
+    assert_eq!(decoded.successor().unwrap(), Some(new.to_name()));
+  }
+
+  #[tokio::test]
+  async fn includes_keys_that_names_dont_embed() {
+    // ECDSA keys are too large to embed, so names parsed from strings don't know their key
+    let old = WritableName::new_ecdsa();
+    let new = WritableName::new();
+    let succession = Succession::sign(&old, &new.to_name()).await.unwrap();
+    assert!(succession.public_key.is_some());
+    succession
+      .verify(&Name::parse(old.to_string()).unwrap())
+      .unwrap();
+  }
+
+  #[tokio::test]
+  async fn follows_chains() {
+    let names: Vec<_> = (0..3).map(|_| WritableName::new()).collect();
+    let mut revisions = vec![];
+    for pair in names.windows(2) {
+      let succession = Succession::sign(&pair[0], &pair[1].to_name())
+        .await
+        .unwrap();
+      revisions.push(Revision::v0(&pair[0].to_name(), "").increment_succession(&succession));
+    }
+    revisions.push(Revision::v0(&names[2].to_name(), "/ipfs/current"));
+
+    let mut chain = SuccessionChain::new();
+    let mut next = vec![];
+    for revision in revisions {
+      next.push(chain.push(revision).unwrap());
+    }
+    assert_eq!(
+      next,
+      [Some(names[1].to_name()), Some(names[2].to_name()), None]
+    );
+    assert!(chain.is_rotated());
+    assert_eq!(chain.current().value(), b"/ipfs/current");
+    assert_eq!(chain.names().count(), 3);
+  }
+
+  #[tokio::test]
+  async fn rejects_cycles_and_undone_rotations() {
+    let a = WritableName::new();
+    let b = WritableName::new();
+    let a_to_b = Succession::sign(&a, &b.to_name()).await.unwrap();
+    let b_to_a = Succession::sign(&b, &a.to_name()).await.unwrap();
+
+    let mut chain = SuccessionChain::new();
+    chain
+      .push(Revision::v0(&a.to_name(), "").increment_succession(&a_to_b))
+      .unwrap();
+    let err = chain
+      .push(Revision::v0(&b.to_name(), "").increment_succession(&b_to_a))
+      .unwrap_err();
+    assert!(format!("{err:?}").contains("cycle"));
+
+    // a later revision that points elsewhere ends the succession, even though it keeps the extension field
+    let undone = Revision::v0(&a.to_name(), "")
+      .increment_succession(&a_to_b)
+      .increment("/ipfs/back");
+    assert_eq!(undone.successor().unwrap(), None);
+    assert!(undone.succession().unwrap().is_some());
+  }
+}
@@ -0,0 +1,155 @@
+use std::fmt::Display;
+
+use cid::Cid;
+use error_stack::{report, IntoReport, Result, ResultExt};
+
+use crate::{error::InvalidValue, Name};
+
+const IPFS_PREFIX: &str = "/ipfs/";
+const IPNS_PREFIX: &str = "/ipns/";
+
+/// `Value` is a validated content path, suitable for publishing as the value of a name record.
+///
+/// Two kinds of paths are supported:
+/// - `/ipfs/<cid>`, which points to immutable content on IPFS.
+/// - `/ipns/<name>`, which points to another IPNS name.
+///
+/// Either kind may be followed by a path inside the referenced content, e.g. `/ipfs/<cid>/index.html`.
+///
+/// To publish a `Value`, create a [Revision](crate::Revision) with [Revision::v0_value](crate::Revision::v0_value)
+/// or [Revision::increment_value](crate::Revision::increment_value).
+///
+/// ## Example
+///
+/// ```rust
+/// # fn main() -> error_stack::Result<(), w3name::error::InvalidValue> {
+/// use w3name::Value;
+///
+/// let value = Value::parse("/ipfs/bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi/index.html")?;
+/// assert!(matches!(value, Value::Ipfs { .. }));
+/// assert_eq!(value.path(), Some("index.html"));
+///
+/// // typos are caught before anything is signed
+/// assert!(Value::parse("/ipfs/bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzd").is_err());
+/// assert!(Value::parse("ipfs/bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi").is_err());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+  /// An `/ipfs/<cid>` path, with an optional path inside the content.
+  Ipfs { cid: Cid, path: Option<String> },
+
+  /// An `/ipns/<name>` path, with an optional path inside the content.
+  Ipns { name: Name, path: Option<String> },
+}
+
+impl Value {
+  /// Returns a `Value` that points to the given [Cid].
+  pub fn ipfs(cid: Cid) -> Value {
+    Value::Ipfs { cid, path: None }
+  }
+
+  /// Returns a `Value` that points to the given [Name].
+  pub fn ipns(name: &Name) -> Value {
+    Value::Ipns {
+      name: name.clone(),
+      path: None,
+    }
+  }
+
+  /// Parses a `Value` from an `/ipfs/<cid>` or `/ipns/<name>` content path.
+  ///
+  /// IPNS names are parsed with [Name::parse].
+  pub fn parse<S: AsRef<str>>(s: S) -> Result<Value, InvalidValue> {
+    let s = s.as_ref();
+    if let Some(rest) = s.strip_prefix(IPFS_PREFIX) {
+      let (root, path) = split_path(rest);
+      let cid = Cid::try_from(root)
+        .report()
+        .change_context(InvalidValue)
+        .attach_printable_lazy(|| format!("invalid CID in content path: {s}"))?;
+      Ok(Value::Ipfs { cid, path })
+    } else if let Some(rest) = s.strip_prefix(IPNS_PREFIX) {
+      let (root, path) = split_path(rest);
+      let name = Name::parse(root)
+        .change_context(InvalidValue)
+        .attach_printable_lazy(|| format!("invalid name in content path: {s}"))?;
+      Ok(Value::Ipns { name, path })
+    } else {
+      Err(report!(InvalidValue).attach_printable(format!(
+        "content path must start with /ipfs/ or /ipns/: {s}"
+      )))
+    }
+  }
+
+  /// Returns the path inside the referenced content, if any, without a leading slash.
+  pub fn path(&self) -> Option<&str> {
+    match self {
+      Value::Ipfs { path, .. } | Value::Ipns { path, .. } => path.as_deref(),
+    }
+  }
+}
+
+impl Display for Value {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Value::Ipfs { cid, .. } => write!(f, "{IPFS_PREFIX}{cid}")?,
+      Value::Ipns { name, .. } => write!(f, "{IPNS_PREFIX}{name}")?,
+    }
+    match self.path() {
+      Some(path) => write!(f, "/{path}"),
+      None => Ok(()),
+    }
+  }
+}
+
+/// Splits the part of a content path after the `/ipfs/` or `/ipns/` prefix into the root and the remaining path.
+fn split_path(s: &str) -> (&str, Option<String>) {
+  match s.split_once('/') {
+    Some((root, path)) if !path.is_empty() => (root, Some(path.to_string())),
+    Some((root, _)) => (root, None),
+    None => (s, None),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const CID_V1: &str = "bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi";
+  const CID_V0: &str = "QmPFpDRC87jTdSYxjnEZUTjJuYF5yLRWxir3DzJ1XiVZ3t";
+  const NAME: &str = "k51qzi5uqu5dka3tmn6ipgsrq1u2bkuowdwlqcw0vibledypt1y9y5i8v8xwvu";
+
+  #[test]
+  fn parse_ipfs_path() {
+    let value = Value::parse(format!("/ipfs/{CID_V1}")).unwrap();
+    assert_eq!(value, Value::ipfs(Cid::try_from(CID_V1).unwrap()));
+    assert_eq!(value.to_string(), format!("/ipfs/{CID_V1}"));
+
+    let value = Value::parse(format!("/ipfs/{CID_V0}/a/b.txt")).unwrap();
+    assert_eq!(value.path(), Some("a/b.txt"));
+    assert_eq!(value.to_string(), format!("/ipfs/{CID_V0}/a/b.txt"));
+
+    // a trailing slash doesn't add an empty path
+    let value = Value::parse(format!("/ipfs/{CID_V1}/")).unwrap();
+    assert_eq!(value.path(), None);
+  }
+
+  #[test]
+  fn parse_ipns_path() {
+    let value = Value::parse(format!("/ipns/{NAME}")).unwrap();
+    assert_eq!(value, Value::ipns(&Name::parse(NAME).unwrap()));
+    assert_eq!(value.to_string(), format!("/ipns/{NAME}"));
+  }
+
+  #[test]
+  fn parse_invalid_paths() {
+    assert!(Value::parse(CID_V1).is_err());
+    assert!(Value::parse("/ipfs/").is_err());
+    assert!(Value::parse("/ipfs/not-a-cid").is_err());
+    assert!(Value::parse("/ipns/example.com").is_err());
+    assert!(Value::parse(format!("/ipns/{CID_V1}")).is_err());
+    assert!(Value::parse(format!("/ipld/{CID_V1}")).is_err());
+  }
+}
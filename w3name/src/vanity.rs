@@ -0,0 +1,332 @@
+use std::{
+  sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, Mutex, PoisonError,
+  },
+  thread,
+  time::{Duration, Instant},
+};
+
+use error_stack::{report, Result};
+use rand::RngCore;
+use zeroize::Zeroizing;
+
+use crate::{
+  error::{VanityError, VanitySearchCancelled},
+  WritableName,
+};
+
+const BASE36_ALPHABET: &str = "0123456789abcdefghijklmnopqrstuvwxyz";
+
+/// Every ed25519 name starts with this, since the CID header and key type are always the same.
+const FIXED_PREFIX: &str = "k51qzi5uqu5d";
+const NAME_LEN: usize = 62;
+
+/// The number of characters that are effectively random. The character after the fixed prefix only takes a
+/// few values, so it isn't counted.
+const RANDOM_CHARS: usize = NAME_LEN - FIXED_PREFIX.len() - 1;
+
+/// Workers add to the shared attempt counter in batches, so they don't contend on it for every key.
+const BATCH_SIZE: u64 = 64;
+
+/// How often the search checks whether it's done or was cancelled.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Pattern {
+  Suffix(String),
+  Contains(String),
+}
+
+impl Pattern {
+  fn matches(&self, name: &str) -> bool {
+    match self {
+      Pattern::Suffix(suffix) => name.ends_with(suffix.as_str()),
+      Pattern::Contains(s) => name[FIXED_PREFIX.len()..].contains(s.as_str()),
+    }
+  }
+
+  fn expected_attempts(&self) -> f64 {
+    match self {
+      Pattern::Suffix(suffix) => 36f64.powi(suffix.len() as i32),
+      // each position the pattern fits in is another chance to match
+      Pattern::Contains(s) => 36f64.powi(s.len() as i32) / (RANDOM_CHARS - s.len() + 1) as f64,
+    }
+  }
+}
+
+/// A `VanitySearch` generates ed25519 keys until it finds a [WritableName] that matches a pattern, like a name
+/// that ends with `blog`.
+///
+/// Every ed25519 name starts with `k51qzi5uqu5d`, so a pattern can either be a suffix of the name, or appear
+/// anywhere after that prefix. Patterns are made of lowercase base36 characters (`0-9` and `a-z`).
+///
+/// Each extra character makes the search about 36 times slower: a 4 character suffix takes around 1.7 million
+/// attempts, and a 6 character suffix over 2 billion. The search uses every CPU core by default, and reports
+/// its progress with an estimate of the remaining time. It can be cancelled from another thread with a
+/// [VanityCanceller].
+///
+/// ## Example
+///
+/// ```rust
+/// # fn main() -> error_stack::Result<(), w3name::error::VanityError> {
+/// use w3name::VanitySearch;
+///
+/// let search = VanitySearch::suffix("w3")?;
+/// let name = search.run(|progress| {
+///   eprintln!("tried {} keys, {:.0}% chance by now", progress.attempts(), progress.probability() * 100.0);
+/// })?;
+///
+/// assert!(name.to_string().ends_with("w3"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct VanitySearch {
+  pattern: Pattern,
+  threads: usize,
+  progress_interval: Duration,
+  cancelled: Arc<AtomicBool>,
+}
+
+impl VanitySearch {
+  /// Creates a search for a name that ends with `suffix`.
+  pub fn suffix(suffix: &str) -> Result<VanitySearch, VanityError> {
+    Ok(Self::new(Pattern::Suffix(validate_pattern(suffix)?)))
+  }
+
+  /// Creates a search for a name that contains `pattern` after the fixed `k51qzi5uqu5d` prefix.
+  pub fn contains(pattern: &str) -> Result<VanitySearch, VanityError> {
+    Ok(Self::new(Pattern::Contains(validate_pattern(pattern)?)))
+  }
+
+  fn new(pattern: Pattern) -> VanitySearch {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    VanitySearch {
+      pattern,
+      threads,
+      progress_interval: Duration::from_secs(1),
+      cancelled: Arc::new(AtomicBool::new(false)),
+    }
+  }
+
+  /// Sets the number of threads to search with. Defaults to the number of CPU cores.
+  pub fn threads(mut self, threads: usize) -> VanitySearch {
+    self.threads = threads.max(1);
+    self
+  }
+
+  /// Sets how often [run](Self::run) reports progress. Defaults to once per second.
+  pub fn progress_interval(mut self, interval: Duration) -> VanitySearch {
+    self.progress_interval = interval;
+    self
+  }
+
+  /// Returns a handle that cancels this search when [cancel](VanityCanceller::cancel) is called.
+  ///
+  /// Cancelling is permanent: later calls to [run](Self::run) on the same search fail straight away.
+  pub fn canceller(&self) -> VanityCanceller {
+    VanityCanceller(self.cancelled.clone())
+  }
+
+  /// Returns the average number of keys that have to be generated to find a match.
+  pub fn expected_attempts(&self) -> f64 {
+    self.pattern.expected_attempts()
+  }
+
+  /// Generates keys until one matches, calling `on_progress` periodically from the current thread.
+  ///
+  /// This blocks until a match is found, or until the search is cancelled, in which case it fails with a
+  /// [VanitySearchCancelled] error. Use [canceller](Self::canceller) to cancel it from another thread
+  /// or from `on_progress`.
+  pub fn run<F: FnMut(&VanityProgress)>(
+    &self,
+    mut on_progress: F,
+  ) -> Result<WritableName, VanityError> {
+    let attempts = AtomicU64::new(0);
+    let done = AtomicBool::new(false);
+    let found = Mutex::new(None);
+    let start = Instant::now();
+
+    thread::scope(|scope| {
+      for _ in 0..self.threads {
+        scope.spawn(|| self.search(&attempts, &done, &found));
+      }
+
+      let mut last_report = start;
+      while !done.load(Ordering::Relaxed) {
+        if self.cancelled.load(Ordering::Relaxed) {
+          done.store(true, Ordering::Relaxed);
+          break;
+        }
+        thread::sleep(POLL_INTERVAL.min(self.progress_interval));
+        if last_report.elapsed() >= self.progress_interval {
+          last_report = Instant::now();
+          on_progress(&VanityProgress {
+            attempts: attempts.load(Ordering::Relaxed),
+            elapsed: start.elapsed(),
+            expected_attempts: self.expected_attempts(),
+          });
+        }
+      }
+    });
+
+    let found = found.into_inner().unwrap_or_else(PoisonError::into_inner);
+    found.ok_or_else(|| {
+      report!(VanitySearchCancelled {
+        attempts: attempts.into_inner()
+      })
+      .change_context(VanityError)
+    })
+  }
+
+  fn search(&self, attempts: &AtomicU64, done: &AtomicBool, found: &Mutex<Option<WritableName>>) {
+    let mut rng = rand::thread_rng();
+    // from_seed only wipes its own copy of the seed, so the buffer is wiped when the search ends
+    let mut seed = Zeroizing::new([0u8; 32]);
+    while !done.load(Ordering::Relaxed) {
+      let mut batch = 0;
+      while batch < BATCH_SIZE {
+        batch += 1;
+        rng.fill_bytes(&mut *seed);
+        let name = WritableName::from_seed(*seed);
+        if self.pattern.matches(&name.to_string()) {
+          found
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_or_insert(name);
+          done.store(true, Ordering::Relaxed);
+          break;
+        }
+      }
+      attempts.fetch_add(batch, Ordering::Relaxed);
+    }
+  }
+}
+
+/// Cancels a [VanitySearch] from another thread. See [VanitySearch::canceller].
+#[derive(Clone, Debug)]
+pub struct VanityCanceller(Arc<AtomicBool>);
+
+impl VanityCanceller {
+  /// Stops the search. [VanitySearch::run] returns shortly after with a [VanitySearchCancelled] error.
+  pub fn cancel(&self) {
+    self.0.store(true, Ordering::Relaxed);
+  }
+}
+
+/// The progress of a [VanitySearch], as passed to the callback of [VanitySearch::run].
+#[derive(Clone, Debug)]
+pub struct VanityProgress {
+  attempts: u64,
+  elapsed: Duration,
+  expected_attempts: f64,
+}
+
+impl VanityProgress {
+  /// Returns the number of keys generated so far.
+  pub fn attempts(&self) -> u64 {
+    self.attempts
+  }
+
+  /// Returns the time since the search started.
+  pub fn elapsed(&self) -> Duration {
+    self.elapsed
+  }
+
+  /// Returns the average number of keys generated per second so far.
+  pub fn attempts_per_second(&self) -> f64 {
+    self.attempts as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+  }
+
+  /// Returns the average number of keys that have to be generated to find a match.
+  pub fn expected_attempts(&self) -> f64 {
+    self.expected_attempts
+  }
+
+  /// Estimates the remaining time at the current speed, or `None` if nothing has been generated yet.
+  ///
+  /// Every key has the same chance of matching, so the expected remaining time doesn't go down as the search goes
+  /// on: it's the time to generate [expected_attempts](Self::expected_attempts) keys, no matter how many have
+  /// been generated already.
+  pub fn expected_time_remaining(&self) -> Option<Duration> {
+    let rate = self.attempts_per_second();
+    if self.attempts == 0 {
+      return None;
+    }
+    Some(Duration::try_from_secs_f64(self.expected_attempts / rate).unwrap_or(Duration::MAX))
+  }
+
+  /// Returns the probability that a search would have found a match after this many attempts.
+  pub fn probability(&self) -> f64 {
+    1.0 - (-(self.attempts as f64) / self.expected_attempts).exp()
+  }
+}
+
+fn validate_pattern(pattern: &str) -> Result<String, VanityError> {
+  let pattern = pattern.to_ascii_lowercase();
+  if pattern.is_empty() || pattern.len() > RANDOM_CHARS {
+    return Err(report!(VanityError).attach_printable(format!(
+      "pattern must be between 1 and {RANDOM_CHARS} characters"
+    )));
+  }
+  if let Some(c) = pattern.chars().find(|c| !BASE36_ALPHABET.contains(*c)) {
+    return Err(report!(VanityError).attach_printable(format!(
+      "{c:?} can't appear in a name: patterns can only use 0-9 and a-z"
+    )));
+  }
+  Ok(pattern)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn finds_matching_names() {
+    let name = VanitySearch::suffix("Z").unwrap().run(|_| {}).unwrap();
+    let s = name.to_string();
+    assert!(s.starts_with(FIXED_PREFIX) && s.ends_with('z'));
+    assert_eq!(s.len(), NAME_LEN);
+
+    let name = VanitySearch::contains("w3")
+      .unwrap()
+      .threads(2)
+      .run(|_| {})
+      .unwrap();
+    assert!(name.to_string()[FIXED_PREFIX.len()..].contains("w3"));
+  }
+
+  #[test]
+  fn rejects_invalid_patterns() {
+    assert!(VanitySearch::suffix("").is_err());
+    assert!(VanitySearch::suffix("w3-name").is_err());
+    assert!(VanitySearch::contains(&"a".repeat(RANDOM_CHARS + 1)).is_err());
+  }
+
+  #[test]
+  fn reports_progress_and_cancels() {
+    let search = VanitySearch::suffix("w3namew3name")
+      .unwrap()
+      .threads(2)
+      .progress_interval(Duration::from_millis(10));
+    assert_eq!(search.expected_attempts(), 36f64.powi(12));
+
+    let canceller = search.canceller();
+    let mut last = None;
+    let err = search
+      .run(|progress| {
+        if progress.attempts() > 0 {
+          canceller.cancel();
+        }
+        last = Some(progress.clone());
+      })
+      .unwrap_err();
+
+    let cancelled = err.downcast_ref::<VanitySearchCancelled>().unwrap();
+    let last = last.unwrap();
+    assert!(cancelled.attempts >= last.attempts());
+    assert!(last.expected_time_remaining().unwrap() > Duration::from_secs(3600));
+    assert!(last.probability() < 0.01);
+  }
+}